@@ -0,0 +1,393 @@
+use crate::domain::{AgentEvent, AgentRecord, AgentType, DayUsage, EventKind, ScanFailure, TokenInfo};
+use crate::scanner::{session_title, FileInfo, FileScanner, LossyLines};
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use std::path::{Path, PathBuf};
+
+use super::AgentScanner;
+
+const CHAT_HISTORY_FILE: &str = ".aider.chat.history.md";
+const SESSION_HEADER: &str = "# aider chat started at ";
+
+/// Environment variable listing the workspaces searched for Aider
+/// transcripts, separated like `PATH`.
+pub const WORKSPACES_ENV: &str = "AIDER_WORKSPACES";
+
+/// How deep below a workspace root a repository may sit.
+const MAX_DEPTH: usize = 4;
+
+/// Directories that never contain an Aider transcript worth walking into.
+pub(crate) const IGNORED_DIRS: &[&str] = &[".git", "node_modules", "target", ".venv", "venv", "dist", "build"];
+
+/// Aider writes `.aider.chat.history.md` (and `.aider.input.history`) into
+/// the repository it was started in rather than a home directory, so the
+/// scanner searches a set of workspace roots for them.
+///
+/// Only the chat transcript is parsed; the input history repeats the user
+/// prompts already present in the transcript.
+pub struct AiderScanner {
+    workspaces: Vec<PathBuf>,
+}
+
+impl AiderScanner {
+    /// Search the workspaces in `AIDER_WORKSPACES`. Walking the whole home
+    /// directory on every scan is too slow, so nothing is searched when it
+    /// is unset.
+    pub fn from_env() -> Self {
+        let workspaces = std::env::var_os(WORKSPACES_ENV)
+            .map(|paths| std::env::split_paths(&paths).collect())
+            .unwrap_or_default();
+        Self::with_workspaces(workspaces)
+    }
+
+    pub fn with_workspaces(workspaces: Vec<PathBuf>) -> Self {
+        Self { workspaces }
+    }
+
+    fn parse_chat_history(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        let cwd = file_info.path
+            .parent()
+            .map(|p| p.to_string_lossy().to_string());
+
+        let mut records = Vec::new();
+        let mut current: Option<AiderSession> = None;
+
+        let mut lines = LossyLines::open(&file_info.path)?;
+        for line in lines.by_ref() {
+            if let Some(started) = line.strip_prefix(SESSION_HEADER) {
+                let next = AiderSession::new(started.trim(), file_info);
+                // The history has no per-message times, so a session is taken
+                // to run until the next one starts.
+                if let Some(session) = current.take() {
+                    records.push(session.into_record(file_info, cwd.clone(), next.started_at));
+                }
+                current = Some(next);
+            }
+
+            // Lines before the first header belong to no session.
+            if let Some(ref mut session) = current {
+                session.size += line.len() as u64 + 1;
+                session.parse_line(&line);
+            }
+        }
+
+        lines.finish()?;
+        if let Some(session) = current {
+            records.push(session.into_record(file_info, cwd, file_info.modified_at));
+        }
+
+        Ok(records)
+    }
+}
+
+impl AgentScanner for AiderScanner {
+    fn agent_type(&self) -> AgentType {
+        AgentType::Aider
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.workspaces.clone()
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|name| name == CHAT_HISTORY_FILE)
+    }
+
+    fn files(&self, failures: &mut Vec<ScanFailure>) -> Vec<FileInfo> {
+        let mut files = Vec::new();
+        for root in self.roots() {
+            let scanner = FileScanner::new(root)
+                .with_max_depth(MAX_DEPTH)
+                .with_ignored_dirs(IGNORED_DIRS);
+            files.extend(scanner.walk_matching(|p| self.matches(p), failures));
+        }
+        files
+    }
+
+    fn parse(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        self.parse_chat_history(file_info)
+    }
+}
+
+/// One `# aider chat started at ...` section of a transcript.
+struct AiderSession {
+    header: String,
+    started_at: DateTime<Utc>,
+    size: u64,
+    model: Option<String>,
+    sent: u64,
+    received: u64,
+    cache_write: u64,
+    cache_hit: u64,
+    tool_calls: Vec<String>,
+    first_prompt: Option<String>,
+    messages: usize,
+    events: Vec<AgentEvent>,
+}
+
+impl AiderSession {
+    fn new(header: &str, file_info: &FileInfo) -> Self {
+        // The header is written in the user's local time.
+        let started_at = NaiveDateTime::parse_from_str(header, "%Y-%m-%d %H:%M:%S")
+            .ok()
+            .and_then(|naive| Local.from_local_datetime(&naive).single())
+            .map(|local| local.with_timezone(&Utc))
+            .unwrap_or(file_info.created_at);
+
+        Self {
+            header: header.to_string(),
+            started_at,
+            size: 0,
+            model: None,
+            sent: 0,
+            received: 0,
+            cache_write: 0,
+            cache_hit: 0,
+            tool_calls: Vec::new(),
+            first_prompt: None,
+            messages: 0,
+            events: Vec::new(),
+        }
+    }
+
+    /// The transcript has no per-message times, so events carry none.
+    fn push_event(&mut self, kind: EventKind) -> &mut AgentEvent {
+        self.events.push(AgentEvent::new(kind, None));
+        self.events.last_mut().expect("just pushed")
+    }
+
+    fn push_tool_call(&mut self, name: &str) {
+        self.tool_calls.push(name.to_string());
+        self.push_event(EventKind::ToolCall).tool_name = Some(name.to_string());
+    }
+
+    fn parse_line(&mut self, line: &str) {
+        // User input: `#### /run cargo test` is a command, anything else a prompt.
+        if let Some(input) = line.strip_prefix("#### ") {
+            if let Some(command) = input.trim().strip_prefix('/')
+                && let Some(name) = command.split_whitespace().next()
+            {
+                self.push_tool_call(name);
+            } else {
+                self.messages += 1;
+                self.push_event(EventKind::UserPrompt);
+                if self.first_prompt.is_none() {
+                    self.first_prompt = session_title(input);
+                }
+            }
+            return;
+        }
+
+        let Some(note) = line.strip_prefix("> ") else {
+            return;
+        };
+
+        if self.model.is_none()
+            && let Some(rest) = note.strip_prefix("Model: ").or_else(|| note.strip_prefix("Main model: "))
+        {
+            // "Model: gpt-4o with diff edit format"
+            self.model = rest.split_whitespace().next().map(|m| m.to_string());
+        } else if let Some(rest) = note.strip_prefix("Tokens: ") {
+            // Aider reports usage once per reply, so each line is a message.
+            let usage = parse_tokens(rest);
+            self.sent = self.sent.saturating_add(usage.input);
+            self.received = self.received.saturating_add(usage.output);
+            self.cache_write = self.cache_write.saturating_add(usage.cache_creation);
+            self.cache_hit = self.cache_hit.saturating_add(usage.cached);
+            self.messages += 1;
+            let model = self.model.clone();
+            let event = self.push_event(EventKind::AssistantMessage);
+            event.model = model;
+            event.usage = Some(usage);
+        } else if note.starts_with("Applied edit to ") {
+            self.push_tool_call("edit");
+        }
+    }
+
+    fn into_record(self, file_info: &FileInfo, cwd: Option<String>, ended_at: DateTime<Utc>) -> AgentRecord {
+        let tokens = if self.sent > 0 || self.received > 0 {
+            Some(TokenInfo {
+                input: self.sent,
+                output: self.received,
+                cached: self.cache_hit,
+                cache_creation: self.cache_write,
+                reasoning: 0,
+                total: self.sent + self.received + self.cache_hit + self.cache_write,
+            })
+        } else {
+            None
+        };
+
+        // Without per-message times, all of a session's activity is
+        // counted on the day its header gives.
+        let day = DayUsage {
+            date: self.started_at.date_naive(),
+            messages: self.messages,
+            tokens: tokens.as_ref().map_or(0, |t| t.total),
+            tool_calls: self.tool_calls.len(),
+        };
+
+        AgentRecord {
+            agent_type: AgentType::Aider,
+            file_path: file_info.path.to_string_lossy().to_string(),
+            created_at: file_info.created_at,
+            modified_at: file_info.modified_at,
+            started_at: self.started_at,
+            ended_at: ended_at.max(self.started_at),
+            file_size: self.size,
+            // Headers only have second precision, so two repositories can
+            // start a session at the same time; the transcript tells them apart.
+            session_id: Some(format!("{}#{}", file_info.path.display(), self.header)),
+            parent_session_id: None,
+            model: self.model,
+            cwd,
+            title: self.first_prompt,
+            tokens,
+            tool_calls: self.tool_calls,
+            days: vec![day],
+            events: self.events,
+            format: None,
+            cli_version: None,
+            git_branch: None,
+            git_commit: None,
+            repo_root: None,
+            repo_remote: None,
+            source_missing: false,
+        }
+    }
+}
+
+/// Parse "2.4k sent, 1.2k cache write, 3.1k cache hit, 310 received. Cost: ..."
+fn parse_tokens(text: &str) -> TokenInfo {
+    let text = text.split(" Cost:").next().unwrap_or(text);
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut usage = TokenInfo { input: 0, output: 0, cached: 0, cache_creation: 0, reasoning: 0, total: 0 };
+
+    for (i, word) in words.iter().enumerate() {
+        let label = word.trim_end_matches([',', '.']);
+        let amount = match label {
+            "sent" | "received" if i >= 1 => parse_count(words[i - 1]),
+            "write" | "hit" if i >= 2 && words[i - 1] == "cache" => parse_count(words[i - 2]),
+            _ => continue,
+        };
+        match label {
+            "sent" => usage.input = usage.input.saturating_add(amount),
+            "received" => usage.output = usage.output.saturating_add(amount),
+            "write" => usage.cache_creation = usage.cache_creation.saturating_add(amount),
+            _ => usage.cached = usage.cached.saturating_add(amount),
+        }
+    }
+    usage.total = usage.input + usage.output + usage.cached + usage.cache_creation;
+    usage
+}
+
+/// Parse token counts as Aider prints them: "310", "12,345", "2.4k", "1.1M".
+fn parse_count(word: &str) -> u64 {
+    let word = word.replace(',', "");
+    let (number, scale) = match word.chars().last() {
+        Some('k') => (&word[..word.len() - 1], 1_000.0),
+        Some('M') => (&word[..word.len() - 1], 1_000_000.0),
+        _ => (word.as_str(), 1.0),
+    };
+    number.parse::<f64>().map(|n| (n * scale).round() as u64).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn file_info(path: &Path) -> FileInfo {
+        FileInfo { path: path.to_path_buf(), created_at: Utc::now(), modified_at: Utc::now(), size: 0 }
+    }
+
+    #[test]
+    fn counts_are_read_as_aider_prints_them() {
+        for (word, expected) in [
+            ("310", 310),
+            ("12,345", 12_345),
+            ("2.4k", 2_400),
+            ("1.2k", 1_200),
+            ("1.1M", 1_100_000),
+            ("n/a", 0),
+        ] {
+            assert_eq!(parse_count(word), expected, "{}", word);
+        }
+    }
+
+    /// Lines fed to a session; expected sent, received, cache write and cache
+    /// hit tokens; tool calls; first prompt.
+    type LineCase<'a> = (&'a [&'a str], (u64, u64, u64, u64), &'a [&'a str], Option<&'a str>);
+
+    #[test]
+    fn token_and_input_lines() {
+        let info = file_info(Path::new("/repo/.aider.chat.history.md"));
+        let cases: &[LineCase] = &[
+            (&["> Tokens: 1.2k sent, 300 received."], (1_200, 300, 0, 0), &[], None),
+            (
+                &["> Tokens: 2.4k sent, 1.2k cache write, 3.1k cache hit, 310 received. Cost: $0.01 message"],
+                (2_400, 310, 1_200, 3_100),
+                &[],
+                None,
+            ),
+            (&["> Tokens: 100 sent, 10 received.", "> Tokens: 50 sent, 5 received."], (150, 15, 0, 0), &[], None),
+            (&["#### /run cargo test", "#### /add src/main.rs"], (0, 0, 0, 0), &["run", "add"], None),
+            (&["#### fix the login bug", "#### /test"], (0, 0, 0, 0), &["test"], Some("fix the login bug")),
+            (&["> Applied edit to src/lib.rs"], (0, 0, 0, 0), &["edit"], None),
+        ];
+        for (lines, (sent, received, write, hit), tools, prompt) in cases {
+            let mut session = AiderSession::new("2025-01-02 03:04:05", &info);
+            lines.iter().for_each(|line| session.parse_line(line));
+            assert_eq!((session.sent, session.received, session.cache_write, session.cache_hit), (*sent, *received, *write, *hit), "{:?}", lines);
+            assert_eq!(session.tool_calls, *tools, "{:?}", lines);
+            assert_eq!(session.first_prompt.as_deref(), *prompt, "{:?}", lines);
+        }
+    }
+
+    #[test]
+    fn headers_start_sessions() {
+        let info = file_info(Path::new("/repo/.aider.chat.history.md"));
+        let expected = Local.with_ymd_and_hms(2025, 1, 2, 3, 4, 5).unwrap().with_timezone(&Utc);
+        assert_eq!(AiderSession::new("2025-01-02 03:04:05", &info).started_at, expected);
+        // An unreadable header falls back to the file's creation time
+        assert_eq!(AiderSession::new("yesterday", &info).started_at, info.created_at);
+    }
+
+    #[test]
+    fn sessions_are_split_on_headers_and_keyed_by_file() {
+        let dir = std::env::temp_dir().join(format!("agent-insights-aider-{}", std::process::id()));
+        let transcript = "preamble\n\
+            # aider chat started at 2025-01-02 03:04:05\n\
+            > Main model: gpt-4o with diff edit format\n\
+            #### add a test\n\
+            > Tokens: 1k sent, 200 received.\n\
+            # aider chat started at 2025-01-02 04:00:00\n\
+            #### /run cargo test\n";
+        let mut ids = Vec::new();
+        for repo in ["a", "b"] {
+            let path = dir.join(repo).join(CHAT_HISTORY_FILE);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, transcript).unwrap();
+            let records = AiderScanner::with_workspaces(vec![dir.clone()]).parse(&file_info(&path)).unwrap();
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[0].model.as_deref(), Some("gpt-4o"));
+            assert_eq!(records[0].title.as_deref(), Some("add a test"));
+            assert_eq!(records[0].tokens.as_ref().unwrap().total, 1_200);
+            assert_eq!(records[0].ended_at, records[1].started_at);
+            assert_eq!(records[1].tool_calls, vec!["run"]);
+            let day = &records[0].days[0];
+            assert_eq!(records[0].days.len(), 1);
+            assert_eq!((day.date, day.messages, day.tokens, day.tool_calls), (records[0].started_at.date_naive(), 2, 1_200, 0));
+            let kinds: Vec<EventKind> = records[0].events.iter().map(|e| e.kind).collect();
+            assert_eq!(kinds, vec![EventKind::UserPrompt, EventKind::AssistantMessage]);
+            assert_eq!(records[0].events[1].model.as_deref(), Some("gpt-4o"));
+            assert_eq!(records[0].events[1].usage.as_ref().unwrap().total, 1_200);
+            assert_eq!(records[1].events[0].tool_name.as_deref(), Some("run"));
+            ids.extend(records.into_iter().filter_map(|r| r.session_id));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 4);
+    }
+}
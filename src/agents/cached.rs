@@ -0,0 +1,152 @@
+use crate::cache::CacheDb;
+use crate::domain::{AgentRecord, AgentType, ScanFailure};
+use crate::scanner::FileInfo;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::AgentScanner;
+
+/// Wraps any scanner so that files unchanged since they were last parsed
+/// are answered from the cache, and parsed sessions outlive their files.
+///
+/// Files holding several sessions (Aider transcripts, Crush databases) are
+/// cached and archived as a whole, so their sessions come and go together.
+pub struct CachedScanner {
+    inner: Arc<dyn AgentScanner>,
+    cache: Arc<CacheDb>,
+}
+
+impl CachedScanner {
+    pub fn new(inner: Arc<dyn AgentScanner>, cache: Arc<CacheDb>) -> Self {
+        Self { inner, cache }
+    }
+}
+
+impl AgentScanner for CachedScanner {
+    fn agent_type(&self) -> AgentType {
+        self.inner.agent_type()
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        self.inner.roots()
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        self.inner.matches(path)
+    }
+
+    fn parse(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        self.inner.parse(file_info)
+    }
+
+    /// Read with the wrapped scanner and cache the result.
+    fn read(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        let records = self.inner.read(file_info)?;
+        let path = file_info.path.to_string_lossy();
+        let cached = self.cache
+            .cache_records(&path, &self.agent_type(), &file_info.modified_at, &records, self.parser_version())
+            .and_then(|_| self.cache.store_content(&path, &file_info.modified_at, &file_info.path));
+        if let Err(e) = cached {
+            tracing::warn!("Failed to cache {:?}: {}", file_info.path, e);
+        }
+        Ok(records)
+    }
+
+    fn files(&self, failures: &mut Vec<ScanFailure>) -> Vec<FileInfo> {
+        self.inner.files(failures)
+    }
+
+    fn parser_version(&self) -> u32 {
+        self.inner.parser_version()
+    }
+
+    fn cached(&self, file_info: &FileInfo) -> Option<Vec<AgentRecord>> {
+        let path = file_info.path.to_string_lossy();
+        if let Ok(Some(record)) = self.cache.get_cached_record(&path, &file_info.modified_at, self.parser_version()) {
+            return Some(vec![record]);
+        }
+        self.cache
+            .get_cached_file_records(&path, &file_info.modified_at, self.parser_version())
+            .ok()?
+    }
+
+    fn uses_cache(&self) -> bool {
+        true
+    }
+
+    fn watch_roots(&self) -> Vec<PathBuf> {
+        self.inner.watch_roots()
+    }
+
+    fn owns(&self, path: &Path) -> bool {
+        self.inner.owns(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::aider::AiderScanner;
+    use crate::agents::custom::{CustomAgentDefinition, CustomScanner};
+    use crate::cache::MissingFiles;
+    use std::fs;
+
+    #[test]
+    fn unchanged_files_are_answered_from_the_cache() {
+        let dir = std::env::temp_dir().join(format!("agent-insights-cached-{}", std::process::id()));
+        let sessions = dir.join("sessions");
+        fs::create_dir_all(&sessions).unwrap();
+        fs::write(sessions.join("a.jsonl"), r#"{"id":"a","in":3,"out":4}"#).unwrap();
+        fs::write(sessions.join("b.jsonl"), r#"{"id":"b","in":1,"out":2}"#).unwrap();
+        let definition: CustomAgentDefinition = serde_json::from_value(serde_json::json!({
+            "name": "Forge",
+            "root": sessions.to_string_lossy(),
+            "glob": "*.jsonl",
+            "format": "jsonl",
+            "fields": { "session_id": "/id", "input_tokens": "/in", "output_tokens": "/out" }
+        }))
+        .unwrap();
+        let cache = Arc::new(CacheDb::new(Some(dir.join("cache.db"))).unwrap());
+        let scanner = CachedScanner::new(Arc::new(CustomScanner::new(definition, "/nonexistent").unwrap()), cache);
+
+        let first = scanner.scan_with_progress(&|_| {}).unwrap();
+        let second = scanner.scan_with_progress(&|_| {}).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((first.report.parsed, first.report.cached, first.report.cache_misses), (2, 0, 2));
+        assert_eq!((second.report.parsed, second.report.cached, second.report.cache_misses), (0, 2, 0));
+        let mut tokens: Vec<_> = second.records.iter().map(|r| r.tokens.as_ref().unwrap().total).collect();
+        tokens.sort();
+        assert_eq!(tokens, vec![3, 7]);
+    }
+
+    #[test]
+    fn files_holding_several_sessions_are_cached_and_archived_whole() {
+        let dir = std::env::temp_dir().join(format!("agent-insights-cached-multi-{}", std::process::id()));
+        let transcript = dir.join("repo").join(".aider.chat.history.md");
+        fs::create_dir_all(transcript.parent().unwrap()).unwrap();
+        fs::write(
+            &transcript,
+            "# aider chat started at 2025-01-02 03:04:05\n#### one\n\
+             # aider chat started at 2025-01-02 04:00:00\n#### two\n",
+        )
+        .unwrap();
+        let cache = Arc::new(CacheDb::new(Some(dir.join("cache.db"))).unwrap());
+        let scanner = CachedScanner::new(Arc::new(AiderScanner::with_workspaces(vec![dir.clone()])), cache.clone());
+
+        let first = scanner.scan_with_progress(&|_| {}).unwrap();
+        let second = scanner.scan_with_progress(&|_| {}).unwrap();
+        fs::remove_file(&transcript).unwrap();
+        cache.reconcile_missing(MissingFiles::Archive).unwrap();
+        let archived = cache.get_archived_records(Some(&AgentType::Aider)).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!((first.report.parsed, first.report.cached), (1, 0));
+        assert_eq!((second.report.parsed, second.report.cached), (0, 1));
+        assert_eq!(second.records.len(), 2);
+        let titles: Vec<_> = archived.iter().map(|r| r.title.as_deref()).collect();
+        assert_eq!(titles, vec![Some("one"), Some("two")]);
+        assert!(archived.iter().all(|r| r.source_missing));
+    }
+}
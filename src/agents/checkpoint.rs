@@ -0,0 +1,70 @@
+use crate::cache::{CacheDb, ParseCheckpoint};
+use crate::domain::AgentEvent;
+use crate::scanner::{prefix_hash, read_jsonl_from, FileInfo};
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Running state of a JSONL session parse that can be saved after any
+/// complete line and resumed once more lines are appended.
+///
+/// The saved state holds aggregates and whatever is needed to deduplicate
+/// later lines, not the session's events: those are cached with the
+/// file's record already, and are restored from there.
+pub(crate) trait Resumable: Default + Serialize + DeserializeOwned {
+    fn feed(&mut self, json: &Value);
+
+    /// Count complete lines that were skipped because they did not parse.
+    fn skip_invalid(&mut self, lines: usize);
+
+    fn events(&self) -> &[AgentEvent];
+
+    /// Take back the events gathered before the checkpoint, along with
+    /// anything derived from them that the saved state leaves out.
+    fn restore_events(&mut self, events: Vec<AgentEvent>);
+}
+
+/// A checkpointed parse state and the number of events it had gathered.
+#[derive(Serialize, Deserialize)]
+struct Saved<S> {
+    events: usize,
+    session: S,
+}
+
+/// Parse the JSONL file at `file_info`, resuming from its checkpoint in
+/// `cache` when the file has only been appended to since, and checkpoint
+/// the parse again at its last complete line.
+pub(crate) fn parse_resumable<S: Resumable>(file_info: &FileInfo, cache: &CacheDb, parser_version: u32) -> Result<S> {
+    let path = file_info.path.to_string_lossy();
+    let resumed = cache.get_checkpoint(&path, parser_version).ok().flatten()
+        .filter(|cp| cp.offset <= file_info.size)
+        .filter(|cp| prefix_hash(&file_info.path, cp.offset).ok() == Some(cp.prefix_hash))
+        .and_then(|cp| {
+            let Saved { events, mut session } = serde_json::from_str::<Saved<S>>(&cp.state).ok()?;
+            // The cached events may run past the checkpoint, into the
+            // unterminated last line; without all of them, start over.
+            let mut cached = cache.get_events(&path).ok()?;
+            if cached.len() < events {
+                return None;
+            }
+            cached.truncate(events);
+            session.restore_events(cached);
+            Some((session, cp.offset))
+        });
+    let (mut session, offset) = resumed.unwrap_or_default();
+
+    let read = read_jsonl_from(&file_info.path, offset, |json| session.feed(json))?;
+    session.skip_invalid(read.invalid_lines);
+    let checkpoint = ParseCheckpoint {
+        offset: read.offset,
+        prefix_hash: prefix_hash(&file_info.path, read.offset)?,
+        state: serde_json::to_string(&Saved { events: session.events().len(), session: &session })?,
+    };
+    let _ = cache.save_checkpoint(&path, parser_version, &checkpoint);
+
+    if let Some(json) = read.tail {
+        session.feed(&json);
+    }
+    Ok(session)
+}
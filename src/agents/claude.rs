@@ -1,172 +1,298 @@
 #[cfg(feature = "cache")]
 use crate::cache::CacheDb;
-use crate::domain::{AgentRecord, AgentType, TokenInfo};
-use crate::scanner::{FileInfo, FileScanner};
+#[cfg(feature = "cache")]
+use crate::domain::tool_invocations;
+use crate::domain::{AgentEvent, AgentRecord, AgentType, EventKind, LogFormat, TokenInfo, ToolOutcome};
+use crate::scanner::{parse_timestamp, read_jsonl_from, session_title, ActivityRange, DailyActivity, FileInfo};
 use anyhow::Result;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::fs;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 #[cfg(feature = "cache")]
 use std::sync::Arc;
 
+use super::AgentScanner;
+#[cfg(feature = "cache")]
+use super::checkpoint::{parse_resumable, Resumable};
+
+/// Newest major Claude Code version whose transcripts this parser was
+/// checked against. Newer transcripts are parsed the same way but flagged.
+const NEWEST_KNOWN_MAJOR: u64 = 2;
+
+/// Bumped whenever a change here alters the record read from a transcript,
+/// e.g. how repeated usage lines are counted, so cached records and
+/// checkpoints from the previous parser are discarded.
+const PARSER_VERSION: u32 = 1;
+
 pub struct ClaudeScanner {
-    scanner: FileScanner,
+    root: PathBuf,
     #[cfg(feature = "cache")]
     cache: Option<Arc<CacheDb>>,
 }
 
 impl ClaudeScanner {
-    /// Create a scanner without cache (used when the `cache` feature is disabled).
+    /// Create a scanner that parses every transcript from the start.
     pub fn new(home_dir: &str) -> Self {
         let root = PathBuf::from(home_dir)
             .join(".claude")
             .join("projects");
         Self {
-            scanner: FileScanner::new(root),
+            root,
             #[cfg(feature = "cache")]
             cache: None,
         }
     }
 
+    /// Create a scanner keeping parse checkpoints in `cache`, so a grown
+    /// transcript is parsed from where the last scan stopped.
     #[cfg(feature = "cache")]
     pub fn with_cache(home_dir: &str, cache: Arc<CacheDb>) -> Self {
         let root = PathBuf::from(home_dir)
             .join(".claude")
             .join("projects");
         Self {
-            scanner: FileScanner::new(root),
+            root,
             cache: Some(cache),
         }
     }
 
-    pub fn scan(&self) -> Result<Vec<AgentRecord>> {
-        let files = self.scanner.scan_jsonl_files()?;
-        let mut records = Vec::new();
-        #[cfg(feature = "cache")]
-        let mut cache_hits = 0;
-        #[cfg(feature = "cache")]
-        let mut cache_misses = 0;
+    fn parse_jsonl_file(&self, file_info: &FileInfo) -> Result<AgentRecord> {
+        let mut session = ClaudeSession::default();
+        let read = read_jsonl_from(&file_info.path, 0, |json| session.feed(json))?;
+        session.invalid_lines = read.invalid_lines;
+        if let Some(json) = read.tail {
+            session.feed(&json);
+        }
+        Ok(session.into_record(file_info))
+    }
 
-        for file_info in files {
-            if let Some(file_name) = file_info.path.file_name().and_then(|n| n.to_str()) {
-                tracing::debug!("file_name : {:?}", file_name);
-                if file_name.starts_with("agent-") {
-                    continue;
-                }
-            }
-            #[cfg(feature = "cache")]
-            let record = if let Some(ref cache) = self.cache {
-                if let Ok(Some(cached)) = cache.get_cached_record(&file_info.path.to_string_lossy(), &file_info.modified_at) {
-                    cache_hits += 1;
-                    cached
-                } else {
-                    cache_misses += 1;
-                    let record = self.parse_jsonl_file(&file_info)?;
-                    let _ = cache.cache_record(&record);
-                    record
-                }
-            } else {
-                self.parse_jsonl_file(&file_info)?
-            };
+    /// Parse only what was appended since the last scan when the cache holds
+    /// a checkpoint for a prefix of the file; otherwise parse it whole.
+    #[cfg(feature = "cache")]
+    fn parse_incremental(&self, file_info: &FileInfo, cache: &CacheDb) -> Result<AgentRecord> {
+        let session: ClaudeSession = parse_resumable(file_info, cache, self.parser_version())?;
+        Ok(session.into_record(file_info))
+    }
+}
+
+/// Running state of a Claude transcript parse. All but its events and tool
+/// calls are serialized into the cache, so that a growing file can be
+/// parsed from where it was left.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+struct ClaudeSession {
+    session_id: Option<String>,
+    /// Claude Code version that wrote the transcript.
+    version: Option<String>,
+    agent_id: Option<String>,
+    model: Option<String>,
+    cwd: Option<String>,
+    git_branch: Option<String>,
+    /// Title from a `summary` line, which Claude Code writes once a
+    /// conversation is compacted or resumed.
+    summary: Option<String>,
+    first_prompt: Option<String>,
+    // Claude Code writes one line per content block of an assistant
+    // message, each repeating the message's usage. Keep the last usage
+    // seen per message; lines without an id count on their own.
+    usage_by_message: HashMap<String, (Usage, Option<NaiveDate>)>,
+    unkeyed_usage: Usage,
+    seen_messages: HashSet<String>,
+    #[serde(skip)]
+    tool_calls: Vec<String>,
+    activity: ActivityRange,
+    daily: DailyActivity,
+    #[serde(skip)]
+    events: Vec<AgentEvent>,
+    // Index into `events` of each assistant message, by usage key.
+    message_events: HashMap<String, usize>,
+    line_count: usize,
+    invalid_lines: usize,
+}
 
-            #[cfg(not(feature = "cache"))]
-            let record = self.parse_jsonl_file(&file_info)?;
+impl ClaudeSession {
+    fn feed(&mut self, json: &Value) {
+        self.line_count += 1;
 
-            records.push(record);
+        self.activity.observe_value(json.get("timestamp"));
+        self.daily.observe_value(json.get("timestamp"));
+
+        if self.session_id.is_none()
+            && let Some(sid) = json.get("sessionId").and_then(|v| v.as_str())
+        {
+            self.session_id = Some(sid.to_string());
         }
 
-        #[cfg(feature = "cache")]
-        if self.cache.is_some() {
-            tracing::debug!("Claude scan: {} cache hits, {} cache misses", cache_hits, cache_misses);
+        if self.version.is_none()
+            && let Some(v) = json.get("version").and_then(|v| v.as_str())
+        {
+            self.version = Some(v.to_string());
         }
 
-        Ok(records)
-    }
+        // Sub-agent transcripts tag every line with their own id
+        if self.agent_id.is_none()
+            && let Some(aid) = json.get("agentId").and_then(|v| v.as_str())
+        {
+            self.agent_id = Some(aid.to_string());
+        }
 
-    fn parse_jsonl_file(&self, file_info: &FileInfo) -> Result<AgentRecord> {
-        let content = fs::read_to_string(&file_info.path)?;
-
-        let mut session_id: Option<String> = None;
-        let mut model: Option<String> = None;
-        let mut cwd: Option<String> = None;
-        let mut total_input = 0u64;
-        let mut total_output = 0u64;
-        let mut total_cached = 0u64;
-        let mut total_cache_creation = 0u64;
-        let mut tool_calls: Vec<String> = Vec::new();
-
-        let mut line_count = 0usize;
-        for line in content.lines() {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
-            }
-            line_count += 1;
+        // cwd appears in the first few lines
+        if self.cwd.is_none()
+            && self.line_count <= 3
+            && let Some(c) = json.get("cwd").and_then(|v| v.as_str())
+        {
+            self.cwd = Some(c.to_string());
+        }
 
-            if let Ok(json) = serde_json::from_str::<Value>(line) {
-                if session_id.is_none() {
-                    if let Some(sid) = json.get("sessionId")
-                        .and_then(|v| v.as_str()) {
-                        session_id = Some(sid.to_string());
-                    }
-                }
+        if self.summary.is_none() && json.get("type").and_then(|t| t.as_str()) == Some("summary") {
+            self.summary = json.get("summary").and_then(|v| v.as_str()).and_then(session_title);
+        }
 
-                // cwd appears in the first few lines
-                if cwd.is_none() && line_count <= 3 {
-                    if let Some(c) = json.get("cwd").and_then(|v| v.as_str()) {
-                        cwd = Some(c.to_string());
-                    }
-                }
+        // Empty outside a repository
+        if self.git_branch.is_none()
+            && let Some(b) = json.get("gitBranch").and_then(|v| v.as_str()).filter(|b| !b.is_empty())
+        {
+            self.git_branch = Some(b.to_string());
+        }
 
-                if model.is_none() {
-                    if let Some(m) = json.get("message")
-                        .and_then(|msg| msg.get("model"))
-                        .and_then(|v| v.as_str()) {
-                        model = Some(m.to_string());
+        if self.model.is_none()
+            && let Some(m) = json.get("message").and_then(|msg| msg.get("model")).and_then(|v| v.as_str())
+        {
+            self.model = Some(m.to_string());
+        }
+
+        let at = json.get("timestamp").and_then(parse_timestamp);
+
+        if let Some(message) = json.get("message") {
+            let message_id = message.get("id").and_then(|v| v.as_str());
+            let message_model = message.get("model").and_then(|v| v.as_str());
+            let is_new = match message_id {
+                Some(id) => self.seen_messages.insert(id.to_string()),
+                None => true,
+            };
+            if is_new {
+                self.daily.today().messages += 1;
+
+                match message.get("role").and_then(|r| r.as_str()) {
+                    Some("assistant") => {
+                        if let Some(key) = message_id.or_else(|| json.get("requestId").and_then(|v| v.as_str())) {
+                            self.message_events.insert(key.to_string(), self.events.len());
+                        }
+                        self.events.push(
+                            AgentEvent::new(EventKind::AssistantMessage, at)
+                                .with_model(message_model)
+                                .with_message_id(message_id),
+                        );
                     }
+                    Some("user") if is_prompt(message) => {
+                        self.events.push(AgentEvent::new(EventKind::UserPrompt, at));
+                        let is_meta = json.get("isMeta").and_then(|v| v.as_bool()).unwrap_or(false);
+                        if self.first_prompt.is_none() && !is_meta {
+                            self.first_prompt = prompt_text(message).and_then(session_title);
+                        }
+                    }
+                    _ => {}
                 }
+            }
 
-                if let Some(message) = json.get("message") {
-                    if let Some(content) = message.get("content").and_then(|c| c.as_array()) {
-                        for item in content {
-                            if let Some(typ) = item.get("type").and_then(|t| t.as_str()) {
-                                if typ == "tool_use" {
-                                    if let Some(name) = item.get("name").and_then(|n| n.as_str()) {
-                                        tool_calls.push(name.to_string());
-                                        tracing::debug!("Found tool_use: {}", name);
-                                    } else {
-                                        tracing::debug!("tool_use without name: {:?}", item);
-                                    }
-                                } else {
-                                    tracing::debug!("Skipped non-tool_use type: {}", typ);
-                                }
+            if let Some(content) = message.get("content").and_then(|c| c.as_array()) {
+                for item in content {
+                    if let Some(typ) = item.get("type").and_then(|t| t.as_str()) {
+                        if typ == "tool_use" {
+                            if let Some(name) = item.get("name").and_then(|n| n.as_str()) {
+                                self.tool_calls.push(name.to_string());
+                                self.daily.today().tool_calls += 1;
+                                self.events.push(
+                                    AgentEvent::new(EventKind::ToolCall, at)
+                                        .with_model(message_model)
+                                        .with_tool(Some(name), item.get("id").and_then(|v| v.as_str())),
+                                );
+                                tracing::debug!("Found tool_use: {}", name);
                             } else {
-                                tracing::debug!("Item without type: {:?}", item);
+                                tracing::debug!("tool_use without name: {:?}", item);
                             }
+                        } else if typ == "tool_result" {
+                            self.events.push(
+                                AgentEvent::new(EventKind::ToolResult, at)
+                                    .with_tool(None, item.get("tool_use_id").and_then(|v| v.as_str()))
+                                    .with_outcome(Some(tool_result_outcome(item))),
+                            );
+                        } else {
+                            tracing::debug!("Skipped non-tool_use type: {}", typ);
                         }
                     } else {
-                        tracing::debug!("message.content missing or not array: {:?}", message.get("content"));
+                        tracing::debug!("Item without type: {:?}", item);
                     }
-                } else {
-                    tracing::debug!("No message in JSON line: {}", line);
                 }
+            } else {
+                tracing::debug!("message.content missing or not array: {:?}", message.get("content"));
+            }
+        } else {
+            tracing::debug!("No message in JSON line: {}", json);
+        }
 
-                if let Some(usage) = json.get("usage").or_else(|| json.get("message").and_then(|m| m.get("usage"))) {
-                    if let Some(input) = usage.get("input_tokens").and_then(|v| v.as_u64()) {
-                        total_input = total_input.saturating_add(input);
-                    }
-                    if let Some(output) = usage.get("output_tokens").and_then(|v| v.as_u64()) {
-                        total_output = total_output.saturating_add(output);
-                    }
-                    if let Some(cached) = usage.get("cache_read_input_tokens").and_then(|v| v.as_u64()) {
-                        total_cached = total_cached.saturating_add(cached);
-                    }
-                    if let Some(cache_creation) = usage.get("cache_creation_input_tokens").and_then(|v| v.as_u64()) {
-                        total_cache_creation = total_cache_creation.saturating_add(cache_creation);
-                    }
+        if let Some(usage) = json.get("usage").or_else(|| json.get("message").and_then(|m| m.get("usage"))) {
+            let usage = Usage::from_json(usage);
+            let key = json.get("message")
+                .and_then(|m| m.get("id"))
+                .or_else(|| json.get("requestId"))
+                .and_then(|v| v.as_str());
+            match key {
+                Some(id) => {
+                    self.usage_by_message.insert(id.to_string(), (usage, self.daily.current()));
+                }
+                None => {
+                    self.unkeyed_usage.add(&usage);
+                    self.daily.today().tokens += usage.total();
+                    self.events.push(AgentEvent::new(EventKind::TokenUsage, at).with_usage(Some(usage.token_info())));
                 }
             }
         }
+    }
+
+    fn into_record(self, file_info: &FileInfo) -> AgentRecord {
+        let ClaudeSession {
+            mut session_id,
+            version,
+            agent_id,
+            model,
+            cwd,
+            git_branch,
+            summary,
+            first_prompt,
+            usage_by_message,
+            unkeyed_usage,
+            tool_calls,
+            activity,
+            mut daily,
+            mut events,
+            message_events,
+            invalid_lines,
+            ..
+        } = self;
+
+        let mut totals = unkeyed_usage;
+        let mut unmatched: Vec<(&String, &Usage)> = Vec::new();
+        for (key, (usage, date)) in &usage_by_message {
+            totals.add(usage);
+            daily.day(*date).tokens += usage.total();
+            match message_events.get(key) {
+                Some(&index) => events[index].usage = Some(usage.token_info()),
+                None => unmatched.push((key, usage)),
+            }
+        }
+        unmatched.sort_by_key(|(key, _)| *key);
+        for (_, usage) in unmatched {
+            events.push(AgentEvent::new(EventKind::TokenUsage, None).with_usage(Some(usage.token_info())));
+        }
+        let Usage {
+            input: total_input,
+            output: total_output,
+            cached: total_cached,
+            cache_creation: total_cache_creation,
+        } = totals;
 
         let tokens = if total_input > 0 || total_output > 0 {
             Some(TokenInfo {
@@ -181,17 +307,483 @@ impl ClaudeScanner {
             None
         };
 
-        Ok(AgentRecord {
+        // A sub-agent shares its parent's `sessionId`; report it under its own
+        // id and link it to the parent instead.
+        let parent_session_id = if is_subagent_file(&file_info.path) {
+            let parent = sidecar_parent(&file_info.path).or(session_id.take());
+            session_id = agent_id.or_else(|| {
+                file_info.path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .map(|s| s.trim_start_matches("agent-").to_string())
+            });
+            parent
+        } else {
+            None
+        };
+
+        let mut format = LogFormat::new("transcript");
+        format.invalid_lines = invalid_lines;
+        format.check_major("Claude Code", version.as_deref(), NEWEST_KNOWN_MAJOR);
+        if tokens.is_none() && events.iter().any(|e| e.kind == EventKind::AssistantMessage) {
+            format.flag("assistant messages carry no token usage");
+        }
+
+        let (started_at, ended_at) = activity.or_file_times(file_info.created_at, file_info.modified_at);
+
+        AgentRecord {
             agent_type: AgentType::Claude,
             file_path: file_info.path.to_string_lossy().to_string(),
             created_at: file_info.created_at,
             modified_at: file_info.modified_at,
+            started_at,
+            ended_at,
             file_size: file_info.size,
             session_id,
+            parent_session_id,
             model,
             cwd,
+            title: summary.or(first_prompt),
             tokens,
             tool_calls,
-        })
+            days: daily.into_days(),
+            events,
+            format: Some(format),
+            cli_version: version,
+            git_branch,
+            git_commit: None,
+            repo_root: None,
+            repo_remote: None,
+            source_missing: false,
+        }
+    }
+}
+
+#[cfg(feature = "cache")]
+impl Resumable for ClaudeSession {
+    fn feed(&mut self, json: &Value) {
+        ClaudeSession::feed(self, json);
+    }
+
+    fn skip_invalid(&mut self, lines: usize) {
+        self.invalid_lines += lines;
+    }
+
+    fn events(&self) -> &[AgentEvent] {
+        &self.events
+    }
+
+    fn restore_events(&mut self, events: Vec<AgentEvent>) {
+        self.tool_calls = tool_invocations(&events).into_iter().map(|call| call.tool_name).collect();
+        self.events = events;
+    }
+}
+
+impl AgentScanner for ClaudeScanner {
+    fn agent_type(&self) -> AgentType {
+        AgentType::Claude
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        vec![self.root.clone()]
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.extension().is_some_and(|ext| ext == "jsonl")
+    }
+
+    fn parser_version(&self) -> u32 {
+        PARSER_VERSION
+    }
+
+    /// Parse a transcript, resuming from its checkpoint when it has grown
+    /// since the last scan.
+    fn parse(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        #[cfg(feature = "cache")]
+        if let Some(ref cache) = self.cache {
+            return Ok(vec![self.parse_incremental(file_info, cache)?]);
+        }
+
+        Ok(vec![self.parse_jsonl_file(file_info)?])
+    }
+}
+
+/// Token usage reported for one assistant message.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+struct Usage {
+    input: u64,
+    output: u64,
+    cached: u64,
+    cache_creation: u64,
+}
+
+impl Usage {
+    fn from_json(usage: &Value) -> Self {
+        let get = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+        Self {
+            input: get("input_tokens"),
+            output: get("output_tokens"),
+            cached: get("cache_read_input_tokens"),
+            cache_creation: get("cache_creation_input_tokens"),
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.input + self.output + self.cached + self.cache_creation
+    }
+
+    fn token_info(&self) -> TokenInfo {
+        TokenInfo {
+            input: self.input,
+            output: self.output,
+            cached: self.cached,
+            cache_creation: self.cache_creation,
+            reasoning: 0,
+            total: self.total(),
+        }
+    }
+
+    fn add(&mut self, other: &Usage) {
+        self.input = self.input.saturating_add(other.input);
+        self.output = self.output.saturating_add(other.output);
+        self.cached = self.cached.saturating_add(other.cached);
+        self.cache_creation = self.cache_creation.saturating_add(other.cache_creation);
+    }
+}
+
+/// Claude Code answers a declined permission prompt with an error result
+/// carrying this text.
+const REJECTION_MARKER: &str = "doesn't want to proceed with this tool use";
+
+fn tool_result_outcome(item: &Value) -> ToolOutcome {
+    if !item.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false) {
+        return ToolOutcome::Success;
+    }
+    let rejected = match item.get("content") {
+        Some(Value::String(text)) => text.contains(REJECTION_MARKER),
+        Some(Value::Array(blocks)) => blocks.iter().any(|block| {
+            block.get("text")
+                .and_then(|t| t.as_str())
+                .is_some_and(|text| text.contains(REJECTION_MARKER))
+        }),
+        _ => false,
+    };
+    if rejected { ToolOutcome::Rejected } else { ToolOutcome::Error }
+}
+
+/// Whether a user message carries typed input rather than only tool results.
+fn is_prompt(message: &Value) -> bool {
+    match message.get("content") {
+        Some(Value::String(_)) => true,
+        Some(Value::Array(items)) => items
+            .iter()
+            .any(|item| item.get("type").and_then(|t| t.as_str()) == Some("text")),
+        _ => false,
+    }
+}
+
+/// Text of a typed prompt. Slash commands and their output are logged as
+/// user messages wrapped in tags, and are not what the session was about.
+fn prompt_text(message: &Value) -> Option<&str> {
+    let text = match message.get("content")? {
+        Value::String(text) => text.as_str(),
+        Value::Array(items) => items
+            .iter()
+            .find(|item| item.get("type").and_then(|t| t.as_str()) == Some("text"))?
+            .get("text")?
+            .as_str()?,
+        _ => return None,
+    };
+    let trimmed = text.trim_start();
+    (!trimmed.starts_with('<') && !trimmed.starts_with("Caveat:")).then_some(text)
+}
+
+/// Sub-agent (Task tool) transcripts are written as `agent-<id>.jsonl`, either
+/// next to the parent session or in its `<sessionId>/subagents/` sidecar.
+fn is_subagent_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|name| name.starts_with("agent-"))
+}
+
+/// The parent session id implied by a `<sessionId>/subagents/agent-*.jsonl` path.
+fn sidecar_parent(path: &Path) -> Option<String> {
+    let dir = path.parent()?;
+    if dir.file_name()? != "subagents" {
+        return None;
+    }
+    dir.parent()?
+        .file_name()?
+        .to_str()
+        .map(|s| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use std::fs;
+
+    fn parse(name: &str, lines: &[&str]) -> AgentRecord {
+        let dir = std::env::temp_dir().join(format!("agent-insights-claude-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let file_info = FileInfo {
+            path: path.clone(),
+            created_at: Utc::now(),
+            modified_at: Utc::now(),
+            size: 0,
+        };
+        let record = ClaudeScanner::new("/nonexistent").parse_jsonl_file(&file_info).unwrap();
+        fs::remove_file(path).unwrap();
+        record
+    }
+
+    #[test]
+    fn newer_version_and_missing_usage_are_flagged() {
+        let known = r#"{"sessionId":"s1","version":"2.0.14","message":{"id":"m1","role":"assistant","content":[],"usage":{"input_tokens":1,"output_tokens":2}}}"#;
+        let newer = r#"{"sessionId":"s2","version":"3.1.0","message":{"id":"m1","role":"assistant","content":[],"usage":{"input_tokens":1,"output_tokens":2}}}"#;
+        let no_usage = r#"{"sessionId":"s3","version":"2.0.14","message":{"id":"m1","role":"assistant","content":[],"tokens":{"in":1}}}"#;
+
+        let known = parse("known.jsonl", &[known]);
+        assert_eq!(known.cli_version.as_deref(), Some("2.0.14"));
+        assert_eq!(known.format.unwrap().drift, None);
+        assert!(parse("newer.jsonl", &[newer]).format.unwrap().drift.unwrap().contains("3.1.0"));
+        assert!(parse("no-usage.jsonl", &[no_usage]).format.unwrap().drift.is_some());
+    }
+
+    #[test]
+    fn cli_version_is_the_first_one_logged() {
+        let summary = r#"{"type":"summary","summary":"Fix the build"}"#;
+        let first = r#"{"sessionId":"s1","version":"2.0.14","type":"user","message":{"role":"user","content":"hi"}}"#;
+        let updated = r#"{"sessionId":"s1","version":"2.0.15","type":"user","message":{"role":"user","content":"again"}}"#;
+
+        assert_eq!(parse("versioned.jsonl", &[summary, first, updated]).cli_version.as_deref(), Some("2.0.14"));
+        assert_eq!(parse("unversioned.jsonl", &[summary]).cli_version, None);
+    }
+
+    #[test]
+    fn subagents_next_to_their_parent_roll_up_unless_split() {
+        use crate::services::{AggregateOptions, AggregationService};
+
+        let usage = r#""usage":{"input_tokens":10,"output_tokens":5}"#;
+        let parent = parse("s1.jsonl", &[&format!(
+            r#"{{"sessionId":"s1","timestamp":"2025-03-01T10:00:00Z","message":{{"id":"m1","role":"assistant","content":[],{}}}}}"#,
+            usage
+        )]);
+        let agent = parse("agent-a1b2.jsonl", &[&format!(
+            r#"{{"sessionId":"s1","agentId":"a1b2","timestamp":"2025-03-01T10:01:00Z","message":{{"id":"m2","role":"assistant","content":[],{}}}}}"#,
+            usage
+        )]);
+
+        assert_eq!(parent.parent_session_id, None);
+        assert_eq!(agent.session_id.as_deref(), Some("a1b2"));
+        assert_eq!(agent.parent_session_id.as_deref(), Some("s1"));
+
+        let records = vec![parent, agent];
+        let rolled_up = AggregationService::aggregate_by_agent(records.clone());
+        assert_eq!(rolled_up.len(), 1);
+        assert_eq!(rolled_up["Claude"].total_files, 1);
+        assert_eq!(rolled_up["Claude"].token_stats.total_tokens, 30);
+
+        let split = AggregationService::aggregate_by_agent_with(records, AggregateOptions { split_subagents: true });
+        assert_eq!(split["Claude"].total_files, 1);
+        assert_eq!(split["Claude"].token_stats.total_tokens, 15);
+        assert_eq!(split["Claude sub-agents"].total_files, 1);
+        assert_eq!(split["Claude sub-agents"].token_stats.total_tokens, 15);
+    }
+
+    #[test]
+    fn title_prefers_summary_over_first_typed_prompt() {
+        let command = r#"{"sessionId":"s1","message":{"role":"user","content":"<command-name>/clear</command-name>"}}"#;
+        let prompt = r#"{"sessionId":"s1","message":{"role":"user","content":[{"type":"text","text":"Fix the flaky\n login test"}]}}"#;
+        let summary = r#"{"type":"summary","summary":"Login test flakiness","leafUuid":"u1"}"#;
+
+        assert_eq!(parse("prompt.jsonl", &[command, prompt]).title.as_deref(), Some("Fix the flaky login test"));
+        assert_eq!(parse("summary.jsonl", &[command, prompt, summary]).title.as_deref(), Some("Login test flakiness"));
+    }
+
+    #[test]
+    fn multi_block_assistant_message_counts_usage_once() {
+        let usage = r#""usage":{"input_tokens":10,"output_tokens":200,"cache_read_input_tokens":5000,"cache_creation_input_tokens":300}"#;
+        let thinking = format!(r#"{{"sessionId":"s1","requestId":"req_1","message":{{"id":"msg_1","model":"claude-opus-4","content":[{{"type":"thinking"}}],{}}}}}"#, usage);
+        let text = format!(r#"{{"sessionId":"s1","requestId":"req_1","message":{{"id":"msg_1","model":"claude-opus-4","content":[{{"type":"text"}}],{}}}}}"#, usage);
+        let tool = format!(r#"{{"sessionId":"s1","requestId":"req_1","message":{{"id":"msg_1","model":"claude-opus-4","content":[{{"type":"tool_use","name":"Bash"}}],{}}}}}"#, usage);
+        let second = r#"{"sessionId":"s1","requestId":"req_2","message":{"id":"msg_2","content":[{"type":"text"}],"usage":{"input_tokens":1,"output_tokens":2,"cache_read_input_tokens":3,"cache_creation_input_tokens":4}}}"#;
+
+        let record = parse("multi-block.jsonl", &[&thinking, &text, &tool, second]);
+        let tokens = record.tokens.unwrap();
+
+        assert_eq!(tokens.input, 11);
+        assert_eq!(tokens.output, 202);
+        assert_eq!(tokens.cached, 5003);
+        assert_eq!(tokens.cache_creation, 304);
+        assert_eq!(tokens.total, 11 + 202 + 5003 + 304);
+        assert_eq!(record.tool_calls, vec!["Bash".to_string()]);
+    }
+
+    #[test]
+    fn last_usage_for_a_message_wins() {
+        let partial = r#"{"message":{"id":"msg_1","content":[],"usage":{"input_tokens":10,"output_tokens":1}}}"#;
+        let last = r#"{"message":{"id":"msg_1","content":[],"usage":{"input_tokens":10,"output_tokens":80}}}"#;
+
+        let tokens = parse("last-wins.jsonl", &[partial, last]).tokens.unwrap();
+
+        assert_eq!(tokens.input, 10);
+        assert_eq!(tokens.output, 80);
+    }
+
+    #[test]
+    fn activity_range_comes_from_line_timestamps() {
+        let first = r#"{"sessionId":"s1","timestamp":"2025-03-01T09:00:00Z","message":{"content":[]}}"#;
+        let summary = r#"{"type":"summary","summary":"no timestamp"}"#;
+        let last = r#"{"sessionId":"s1","timestamp":"2025-03-02T01:30:00Z","message":{"content":[]}}"#;
+
+        let record = parse("timestamps.jsonl", &[first, summary, last]);
+
+        assert_eq!(record.started_at.to_rfc3339(), "2025-03-01T09:00:00+00:00");
+        assert_eq!(record.ended_at.to_rfc3339(), "2025-03-02T01:30:00+00:00");
+        assert_eq!(record.date().to_string(), "2025-03-01");
+    }
+
+    #[test]
+    fn session_spanning_midnight_is_split_by_day() {
+        let evening = r#"{"timestamp":"2025-03-01T23:50:00Z","message":{"id":"msg_1","content":[{"type":"tool_use","name":"Read"}],"usage":{"input_tokens":10,"output_tokens":5}}}"#;
+        let streamed = r#"{"timestamp":"2025-03-01T23:50:01Z","message":{"id":"msg_1","content":[{"type":"text"}],"usage":{"input_tokens":10,"output_tokens":5}}}"#;
+        let prompt = r#"{"timestamp":"2025-03-02T00:10:00Z","message":{"role":"user","content":"go on"}}"#;
+        let morning = r#"{"timestamp":"2025-03-02T00:10:05Z","message":{"id":"msg_2","content":[{"type":"tool_use","name":"Bash"},{"type":"tool_use","name":"Edit"}],"usage":{"input_tokens":20,"output_tokens":7}}}"#;
+
+        let record = parse("midnight.jsonl", &[evening, streamed, prompt, morning]);
+        let days: Vec<_> = record.days.iter()
+            .map(|d| (d.date.to_string(), d.messages, d.tokens, d.tool_calls))
+            .collect();
+
+        assert_eq!(days, vec![
+            ("2025-03-01".to_string(), 1, 15, 1),
+            ("2025-03-02".to_string(), 2, 27, 2),
+        ]);
+    }
+
+    #[test]
+    fn emits_normalized_events() {
+        let prompt = r#"{"timestamp":"2025-03-01T10:00:00Z","message":{"role":"user","content":"fix the build"}}"#;
+        let thinking = r#"{"timestamp":"2025-03-01T10:00:03Z","message":{"id":"msg_1","role":"assistant","model":"claude-sonnet-4","content":[{"type":"thinking"}],"usage":{"input_tokens":10,"output_tokens":1}}}"#;
+        let tool = r#"{"timestamp":"2025-03-01T10:00:04Z","message":{"id":"msg_1","role":"assistant","model":"claude-sonnet-4","content":[{"type":"tool_use","id":"toolu_1","name":"Bash"}],"usage":{"input_tokens":10,"output_tokens":40}}}"#;
+        let result = r#"{"timestamp":"2025-03-01T10:00:09Z","message":{"role":"user","content":[{"type":"tool_result","tool_use_id":"toolu_1","is_error":true}]}}"#;
+
+        let record = parse("events.jsonl", &[prompt, thinking, tool, result]);
+        let kinds: Vec<EventKind> = record.events.iter().map(|e| e.kind).collect();
+
+        assert_eq!(kinds, vec![
+            EventKind::UserPrompt,
+            EventKind::AssistantMessage,
+            EventKind::ToolCall,
+            EventKind::ToolResult,
+        ]);
+        let message = &record.events[1];
+        assert_eq!(message.model.as_deref(), Some("claude-sonnet-4"));
+        assert_eq!(message.usage.as_ref().map(|u| u.output), Some(40));
+        assert_eq!(record.events[2].tool_call_id.as_deref(), Some("toolu_1"));
+        assert_eq!(record.events[3].tool_call_id.as_deref(), Some("toolu_1"));
+        assert_eq!(record.events[3].outcome, Some(ToolOutcome::Error));
+    }
+
+    #[test]
+    fn tool_results_pair_with_calls_and_detect_rejection() {
+        let call = |id: &str, name: &str, at: &str| format!(r#"{{"timestamp":"{}","message":{{"id":"msg_{}","role":"assistant","content":[{{"type":"tool_use","id":"{}","name":"{}"}}]}}}}"#, at, id, id, name);
+        let result = |id: &str, at: &str, extra: &str| format!(r#"{{"timestamp":"{}","message":{{"role":"user","content":[{{"type":"tool_result","tool_use_id":"{}"{}}}]}}}}"#, at, id, extra);
+
+        let lines = [
+            call("t1", "Bash", "2025-03-01T10:00:00Z"),
+            result("t1", "2025-03-01T10:00:02.500Z", ""),
+            call("t2", "Edit", "2025-03-01T10:01:00Z"),
+            result("t2", "2025-03-01T10:01:05Z", r#","is_error":true,"content":"The user doesn't want to proceed with this tool use. The tool use was rejected.""#),
+            call("t3", "Bash", "2025-03-01T10:02:00Z"),
+            result("t3", "2025-03-01T10:02:01Z", r#","is_error":true,"content":"Exit code 1""#),
+        ];
+        let lines: Vec<&str> = lines.iter().map(|l| l.as_str()).collect();
+        let record = parse("outcomes.jsonl", &lines);
+
+        let invocations: Vec<_> = crate::domain::tool_invocations(&record.events)
+            .into_iter()
+            .map(|i| (i.tool_name, i.outcome, i.duration_ms))
+            .collect();
+        assert_eq!(invocations, vec![
+            ("Bash".to_string(), Some(ToolOutcome::Success), Some(2500)),
+            ("Edit".to_string(), Some(ToolOutcome::Rejected), Some(5000)),
+            ("Bash".to_string(), Some(ToolOutcome::Error), Some(1000)),
+        ]);
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn appended_lines_resume_from_checkpoint() {
+        let dir = std::env::temp_dir().join(format!("agent-insights-claude-tail-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("tail.jsonl");
+        let cache = CacheDb::new(Some(dir.join("cache.db"))).unwrap();
+        let scanner = ClaudeScanner::new("/nonexistent");
+        let line = |id: &str, output: u64| format!(r#"{{"sessionId":"s1","message":{{"id":"{}","role":"assistant","content":[{{"type":"tool_use","id":"t{}","name":"Read"}}],"usage":{{"input_tokens":1,"output_tokens":{}}}}}}}"#, id, id, output);
+        let parse = |contents: &str| {
+            fs::write(&path, contents).unwrap();
+            let file_info = FileInfo {
+                path: path.clone(),
+                created_at: Utc::now(),
+                modified_at: Utc::now(),
+                size: contents.len() as u64,
+            };
+            let record = scanner.parse_incremental(&file_info, &cache).unwrap();
+            let full = scanner.parse_jsonl_file(&file_info).unwrap();
+            // Events before the checkpoint come back from the cached record
+            assert_eq!(format!("{:?}", record.events), format!("{:?}", full.events));
+            assert_eq!(record.tool_calls, full.tool_calls);
+            cache.cache_record(&record, PARSER_VERSION).unwrap();
+            let checkpoint = cache.get_checkpoint(&path.to_string_lossy(), scanner.parser_version()).unwrap().unwrap();
+            assert!(!checkpoint.state.contains("tool_use") && !checkpoint.state.contains("Read"));
+            (record.tokens.unwrap().output, checkpoint.offset)
+        };
+
+        let first = format!("{}\n", line("m1", 10));
+        assert_eq!(parse(&first), (10, first.len() as u64));
+
+        // An unterminated last line counts but is not checkpointed.
+        let grown = format!("{}{}\n{}", first, line("m2", 20), line("m3", 30));
+        assert_eq!(parse(&grown), (60, (grown.len() - line("m3", 30).len()) as u64));
+
+        // Rewriting the start of the file forces a full parse.
+        let rewritten = format!("{}\n{}\n", line("m9", 5), line("m2", 20));
+        assert_eq!(parse(&rewritten), (25, rewritten.len() as u64));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn records_from_another_parser_version_are_reparsed() {
+        use crate::agents::CachedScanner;
+
+        let dir = std::env::temp_dir().join(format!("agent-insights-claude-version-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("s.jsonl");
+        fs::write(&path, r#"{"sessionId":"s1","message":{"id":"m1","role":"assistant","content":[],"usage":{"input_tokens":1,"output_tokens":2}}}"#).unwrap();
+        let file_info = FileInfo { path: path.clone(), created_at: Utc::now(), modified_at: Utc::now(), size: 0 };
+        let cache = Arc::new(CacheDb::new(Some(dir.join("cache.db"))).unwrap());
+        let scanner = CachedScanner::new(
+            Arc::new(ClaudeScanner::with_cache("/nonexistent", Arc::clone(&cache))),
+            Arc::clone(&cache),
+        );
+
+        let stale = scanner.read(&file_info).unwrap().remove(0);
+        assert!(scanner.cached(&file_info).is_some());
+        cache.cache_record(&stale, PARSER_VERSION - 1).unwrap();
+        assert!(scanner.cached(&file_info).is_none());
+        assert!(cache.get_checkpoint(&path.to_string_lossy(), PARSER_VERSION - 1).unwrap().is_none());
+
+        assert_eq!(cache.clear_agent(&AgentType::Claude).unwrap(), 1);
+        assert!(cache.get_checkpoint(&path.to_string_lossy(), PARSER_VERSION).unwrap().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
     }
 }
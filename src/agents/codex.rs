@@ -1,155 +1,646 @@
-use crate::domain::{AgentRecord, AgentType, TokenInfo};
-use crate::scanner::{FileInfo, FileScanner};
+#[cfg(feature = "cache")]
+use crate::cache::CacheDb;
+#[cfg(feature = "cache")]
+use crate::domain::tool_invocations;
+use crate::domain::{AgentEvent, AgentRecord, AgentType, EventKind, LogFormat, TokenInfo, ToolOutcome};
+use crate::scanner::{
+    parse_timestamp, redact_remote, session_title, ActivityRange, DailyActivity, FileInfo, JsonlReader,
+};
 use anyhow::Result;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "cache")]
+use std::sync::Arc;
+
+use super::AgentScanner;
+#[cfg(feature = "cache")]
+use super::checkpoint::{parse_resumable, Resumable};
 
 pub struct CodexScanner {
-    scanner: FileScanner,
+    root: PathBuf,
+    #[cfg(feature = "cache")]
+    cache: Option<Arc<CacheDb>>,
 }
 
 impl CodexScanner {
+    /// Create a scanner that parses every rollout from the start.
     pub fn new(home_dir: &str) -> Self {
         let root = PathBuf::from(home_dir).join(".codex");
         Self {
-            scanner: FileScanner::new(root),
+            root,
+            #[cfg(feature = "cache")]
+            cache: None,
         }
     }
 
-    pub fn scan(&self) -> Result<Vec<AgentRecord>> {
-        let jsonl_files = self.scanner.scan_jsonl_files()?;
+    /// Create a scanner keeping parse checkpoints in `cache`, so a grown
+    /// rollout is parsed from where the last scan stopped.
+    #[cfg(feature = "cache")]
+    pub fn with_cache(home_dir: &str, cache: Arc<CacheDb>) -> Self {
+        Self { cache: Some(cache), ..Self::new(home_dir) }
+    }
+}
 
-        let mut records = Vec::new();
+/// Newest major Codex CLI version whose rollouts this parser was checked
+/// against.
+const NEWEST_KNOWN_MAJOR: u64 = 0;
 
-        for file_info in jsonl_files.iter() {
-            if let Ok(record) = self.parse_jsonl_file(file_info) {
-                records.push(record);
-            }
+/// Layout of a rollout file, decided by its first line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+enum RolloutFormat {
+    /// `{"type": ..., "payload": ...}` lines opened by `session_meta`.
+    Rollout,
+    /// Early rollouts: a header line with the session id, then bare
+    /// response items with no token counts.
+    Legacy,
+    Unrecognised,
+}
+
+impl RolloutFormat {
+    fn detect(first: &Value) -> Self {
+        if first.get("type").and_then(|t| t.as_str()) == Some("session_meta") {
+            RolloutFormat::Rollout
+        } else if first.get("payload").is_none() && first.get("id").is_some() && first.get("timestamp").is_some() {
+            RolloutFormat::Legacy
+        } else {
+            RolloutFormat::Unrecognised
         }
+    }
 
-        Ok(records)
+    fn schema(self) -> &'static str {
+        match self {
+            RolloutFormat::Rollout => "rollout",
+            RolloutFormat::Legacy => "rollout-legacy",
+            RolloutFormat::Unrecognised => "unrecognised",
+        }
     }
 
-    fn parse_jsonl_file(&self, file_info: &FileInfo) -> Result<AgentRecord> {
-        let content = fs::read_to_string(&file_info.path)?;
+    /// The line's type and payload. Legacy items are their own payload;
+    /// their `record_type` state lines carry nothing we count.
+    fn entry(self, json: &Value) -> Option<(&str, &Value)> {
+        match self {
+            RolloutFormat::Legacy => {
+                json.get("type")?;
+                Some(("response_item", json))
+            }
+            _ => Some((json.get("type")?.as_str()?, json.get("payload")?)),
+        }
+    }
+}
 
-        let mut session_id: Option<String> = None;
-        let mut model: Option<String> = None;
-        let mut cwd: Option<String> = None;
-        let mut total_input = 0u64;
-        let mut total_cached_input = 0u64;
-        let mut total_output = 0u64;
-        let mut total_reasoning = 0u64;
-        let mut total_tokens = 0u64;
-        let mut tool_calls: Vec<String> = Vec::new();
+/// Running state of a rollout parse. All but its events and tool calls are
+/// serialized into the cache, so that a growing rollout can be parsed from
+/// where it was left.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct RolloutSession {
+    session_id: Option<String>,
+    model: Option<String>,
+    cwd: Option<String>,
+    total_input: u64,
+    total_cached_input: u64,
+    total_output: u64,
+    total_reasoning: u64,
+    total_tokens: u64,
+    #[serde(skip)]
+    tool_calls: Vec<String>,
+    activity: ActivityRange,
+    daily: DailyActivity,
+    #[serde(skip)]
+    events: Vec<AgentEvent>,
+    // Model of the current turn; it can change mid-session.
+    turn_model: Option<String>,
+    format: Option<RolloutFormat>,
+    cli_version: Option<String>,
+    git: Option<Value>,
+    title: Option<String>,
+    invalid_lines: usize,
+}
 
-        let mut first_line = true;
-        for line in content.lines() {
-            let line = line.trim();
-            if line.is_empty() {
-                continue;
-            }
+impl RolloutSession {
+    fn feed(&mut self, json: &Value) {
+        // Every rollout line is stamped; session_meta also carries the
+        // session's start time in its payload.
+        self.activity.observe_value(json.get("timestamp"));
+        self.activity.observe_value(json.get("payload").and_then(|p| p.get("timestamp")));
+        self.daily.observe_value(json.get("timestamp"));
 
-            if let Ok(json) = serde_json::from_str::<Value>(line) {
-                // Extract cwd from payload.cwd in the first line
-                if first_line {
-                    first_line = false;
-                    if let Some(c) = json.get("payload")
-                        .and_then(|p| p.get("cwd"))
-                        .and_then(|v| v.as_str())
-                    {
-                        cwd = Some(c.to_string());
-                    }
+        // The first line decides the layout and carries cwd and version
+        let format = match self.format {
+            Some(format) => format,
+            None => {
+                let detected = RolloutFormat::detect(json);
+                let meta = json.get("payload");
+                self.cwd = meta.and_then(|p| p.get("cwd")).and_then(|v| v.as_str()).map(|s| s.to_string());
+                self.cli_version = meta.and_then(|p| p.get("cli_version")).and_then(|v| v.as_str()).map(|s| s.to_string());
+                self.git = meta.and_then(|p| p.get("git")).cloned();
+                if detected == RolloutFormat::Legacy {
+                    self.session_id = json.get("id").and_then(|v| v.as_str()).map(|s| s.to_string());
                 }
+                *self.format.insert(detected)
+            }
+        };
 
-                // Extract data based on event type
-                if let Some(event_type) = json.get("type").and_then(|t| t.as_str()) {
-                    if let Some(payload) = json.get("payload") {
-                        // Extract token usage from event_msg -> token_count
-                        if event_type == "event_msg" {
-                            if let Some(payload_type) = payload.get("type").and_then(|t| t.as_str()) {
-                                if payload_type == "token_count" {
-                                    if let Some(info) = payload.get("info") {
-                                        if let Some(total_usage) = info.get("total_token_usage") {
-                                            if let Some(input) = total_usage.get("input_tokens").and_then(|v| v.as_u64()) {
-                                                total_input = input;
-                                            }
-                                            if let Some(cached) = total_usage.get("cached_input_tokens").and_then(|v| v.as_u64()) {
-                                                total_cached_input = cached;
-                                            }
-                                            if let Some(output) = total_usage.get("output_tokens").and_then(|v| v.as_u64()) {
-                                                total_output = output;
-                                            }
-                                            if let Some(reasoning) = total_usage.get("reasoning_output_tokens").and_then(|v| v.as_u64()) {
-                                                total_reasoning = reasoning;
-                                            }
-                                            if let Some(total) = total_usage.get("total_tokens").and_then(|v| v.as_u64()) {
-                                                total_tokens = total;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
-
-                        // Extract tool calls from response_item -> custom_tool_call
-                        if event_type == "response_item" {
-                            if let Some(payload_type) = payload.get("type").and_then(|t| t.as_str()) {
-                                if payload_type == "custom_tool_call" {
-                                    if let Some(tool_name) = payload.get("name").and_then(|t| t.as_str()) {
-                                        tool_calls.push(tool_name.to_string());
-                                    }
-                                }
-                            }
-                        }
-                    }
+        // Extract data based on event type
+        let Some((event_type, payload)) = format.entry(json) else {
+            return;
+        };
+        let payload_type = payload.get("type").and_then(|t| t.as_str());
+        let at = json.get("timestamp").and_then(parse_timestamp);
+
+        // Extract token usage from event_msg -> token_count
+        if event_type == "event_msg"
+            && payload_type == Some("token_count")
+            && let Some(info) = payload.get("info")
+        {
+            if let Some(total_usage) = info.get("total_token_usage") {
+                if let Some(input) = total_usage.get("input_tokens").and_then(|v| v.as_u64()) {
+                    self.total_input = input;
+                }
+                if let Some(cached) = total_usage.get("cached_input_tokens").and_then(|v| v.as_u64()) {
+                    self.total_cached_input = cached;
+                }
+                if let Some(output) = total_usage.get("output_tokens").and_then(|v| v.as_u64()) {
+                    self.total_output = output;
+                }
+                if let Some(reasoning) = total_usage.get("reasoning_output_tokens").and_then(|v| v.as_u64()) {
+                    self.total_reasoning = reasoning;
                 }
+                if let Some(total) = total_usage.get("total_tokens").and_then(|v| v.as_u64()) {
+                    // Totals are cumulative; the growth since the last
+                    // report belongs to the day of this one.
+                    self.daily.today().tokens += total.saturating_sub(self.total_tokens);
+                    self.total_tokens = total;
+                }
+            }
+            if let Some(last_usage) = info.get("last_token_usage") {
+                self.events.push(
+                    AgentEvent::new(EventKind::TokenUsage, at)
+                        .with_model(self.turn_model.as_deref())
+                        .with_usage(Some(token_info(last_usage))),
+                );
+            }
+        }
 
-                // Extract session ID from session_meta and model from turn_context
-                if let Some(event_type) = json.get("type").and_then(|t| t.as_str()) {
-                    if session_id.is_none() && event_type == "session_meta" {
-                        if let Some(payload) = json.get("payload") {
-                            if let Some(id) = payload.get("id").and_then(|s| s.as_str()) {
-                                session_id = Some(id.to_string());
-                            }
-                        }
-                    }
-                    if model.is_none() && event_type == "turn_context" {
-                        if let Some(m) = json.get("payload")
-                            .and_then(|p| p.get("model"))
-                            .and_then(|v| v.as_str()) {
-                            model = Some(m.to_string());
-                        }
+        // Extract tool calls from every response_item call variant
+        if event_type == "response_item"
+            && let Some(tool_name) = tool_call_name(payload)
+        {
+            self.events.push(
+                AgentEvent::new(EventKind::ToolCall, at)
+                    .with_model(self.turn_model.as_deref())
+                    .with_tool(Some(&tool_name), payload.get("call_id").and_then(|v| v.as_str())),
+            );
+            self.tool_calls.push(tool_name);
+            self.daily.today().tool_calls += 1;
+        }
+
+        if event_type == "response_item" && payload_type == Some("message") {
+            self.daily.today().messages += 1;
+            match payload.get("role").and_then(|r| r.as_str()) {
+                Some("user") if !is_injected_context(payload) => {
+                    self.events.push(AgentEvent::new(EventKind::UserPrompt, at));
+                    if self.title.is_none() {
+                        self.title = input_text(payload).and_then(session_title);
                     }
                 }
+                Some("assistant") => {
+                    self.events.push(
+                        AgentEvent::new(EventKind::AssistantMessage, at)
+                            .with_model(self.turn_model.as_deref())
+                            .with_message_id(payload.get("id").and_then(|v| v.as_str())),
+                    );
+                }
+                _ => {}
             }
         }
 
-        let tokens = if total_input > 0 || total_output > 0 {
+        if event_type == "response_item"
+            && matches!(payload_type, Some("function_call_output") | Some("custom_tool_call_output"))
+        {
+            self.events.push(
+                AgentEvent::new(EventKind::ToolResult, at)
+                    .with_tool(None, payload.get("call_id").and_then(|v| v.as_str()))
+                    .with_outcome(output_outcome(payload.get("output"))),
+            );
+        }
+
+        // Extract session ID from session_meta and model from turn_context
+        if self.session_id.is_none()
+            && event_type == "session_meta"
+            && let Some(id) = payload.get("id").and_then(|s| s.as_str())
+        {
+            self.session_id = Some(id.to_string());
+        }
+        if event_type == "turn_context"
+            && let Some(m) = payload.get("model").and_then(|v| v.as_str())
+        {
+            if self.model.is_none() {
+                self.model = Some(m.to_string());
+            }
+            self.turn_model = Some(m.to_string());
+        }
+    }
+
+    fn into_record(self, file_info: &FileInfo, agent_type: AgentType) -> AgentRecord {
+        let tokens = if self.total_input > 0 || self.total_output > 0 {
             Some(TokenInfo {
-                input: total_input,
-                output: total_output,
-                cached: total_cached_input,
+                input: self.total_input,
+                output: self.total_output,
+                cached: self.total_cached_input,
                 cache_creation: 0,
-                reasoning: total_reasoning,
-                total: total_tokens,
+                reasoning: self.total_reasoning,
+                total: self.total_tokens,
             })
         } else {
             None
         };
 
-        Ok(AgentRecord {
-            agent_type: AgentType::Codex,
+        let format = self.format.unwrap_or(RolloutFormat::Unrecognised);
+        let mut log_format = LogFormat::new(format.schema());
+        log_format.invalid_lines = self.invalid_lines;
+        log_format.check_major("Codex CLI", self.cli_version.as_deref(), NEWEST_KNOWN_MAJOR);
+        let replied = self.events.iter().any(|e| e.kind == EventKind::AssistantMessage);
+        match format {
+            RolloutFormat::Unrecognised => log_format.flag("first line is neither session_meta nor a legacy header"),
+            RolloutFormat::Rollout if tokens.is_none() && replied => {
+                log_format.flag("assistant messages but no token_count events")
+            }
+            _ => {}
+        }
+
+        // session_meta records the checkout the session started in
+        let git_field = |key: &str| {
+            self.git.as_ref()
+                .and_then(|g| g.get(key))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+        };
+
+        let (started_at, ended_at) = self.activity.or_file_times(file_info.created_at, file_info.modified_at);
+
+        AgentRecord {
+            agent_type,
             file_path: file_info.path.to_string_lossy().to_string(),
             created_at: file_info.created_at,
             modified_at: file_info.modified_at,
+            started_at,
+            ended_at,
             file_size: file_info.size,
-            session_id,
-            model,
-            cwd,
+            session_id: self.session_id,
+            parent_session_id: None,
+            model: self.model,
+            cwd: self.cwd,
+            title: self.title,
             tokens,
-            tool_calls,
+            tool_calls: self.tool_calls,
+            days: self.daily.into_days(),
+            events: self.events,
+            format: Some(log_format),
+            cli_version: self.cli_version,
+            git_branch: git_field("branch"),
+            git_commit: git_field("commit_hash"),
+            repo_root: None,
+            repo_remote: git_field("repository_url").map(|url| redact_remote(&url)),
+            source_missing: false,
+        }
+    }
+}
+
+#[cfg(feature = "cache")]
+impl Resumable for RolloutSession {
+    fn feed(&mut self, json: &Value) {
+        RolloutSession::feed(self, json);
+    }
+
+    fn skip_invalid(&mut self, lines: usize) {
+        self.invalid_lines += lines;
+    }
+
+    fn events(&self) -> &[AgentEvent] {
+        &self.events
+    }
+
+    fn restore_events(&mut self, events: Vec<AgentEvent>) {
+        self.tool_calls = tool_invocations(&events).into_iter().map(|call| call.tool_name).collect();
+        self.events = events;
+    }
+}
+
+/// Parse a Codex rollout JSONL file (`session_meta`, `turn_context`,
+/// `event_msg` and `response_item` lines, or the earlier bare-item layout).
+/// Shared with agents that drive Codex and persist its rollouts under their
+/// own directory.
+pub(crate) fn parse_rollout(file_info: &FileInfo, agent_type: AgentType) -> Result<AgentRecord> {
+    let mut session = RolloutSession::default();
+    let mut lines = JsonlReader::open(&file_info.path)?;
+    for json in lines.by_ref() {
+        session.feed(&json);
+    }
+    lines.finish()?;
+    if lines.invalid_lines() > 0 {
+        tracing::debug!("Skipped {} invalid lines in {:?}", lines.invalid_lines(), file_info.path);
+    }
+    session.invalid_lines = lines.invalid_lines();
+    Ok(session.into_record(file_info, agent_type))
+}
+
+/// [`parse_rollout`], resuming from the checkpoint kept in `cache` when the
+/// rollout has only grown since it was last parsed.
+#[cfg(feature = "cache")]
+pub(crate) fn parse_rollout_resumable(
+    file_info: &FileInfo,
+    agent_type: AgentType,
+    cache: &CacheDb,
+    parser_version: u32,
+) -> Result<AgentRecord> {
+    let session: RolloutSession = parse_resumable(file_info, cache, parser_version)?;
+    Ok(session.into_record(file_info, agent_type))
+}
+
+/// Built-in tools that run a command line.
+const SHELL_TOOLS: &[&str] = &["shell", "container.exec", "local_shell", "exec_command"];
+
+/// Normalized tool name for a `response_item` that invokes a tool.
+///
+/// Shell calls become `shell:<program>` so they can be told apart by
+/// command, and MCP tools (`<server>__<tool>`) are reported as
+/// `mcp__<server>__<tool>`, the form Claude Code uses.
+fn tool_call_name(payload: &Value) -> Option<String> {
+    let name = match payload.get("type").and_then(|t| t.as_str())? {
+        "function_call" | "custom_tool_call" => payload.get("name").and_then(|n| n.as_str())?,
+        "local_shell_call" => "local_shell",
+        _ => return None,
+    };
+
+    if SHELL_TOOLS.contains(&name) {
+        let program = shell_program(payload);
+        return Some(match program {
+            Some(program) => format!("shell:{}", program),
+            None => "shell".to_string(),
+        });
+    }
+
+    let unprefixed = name.strip_prefix("mcp__").unwrap_or(name);
+    if let Some((server, tool)) = unprefixed.split_once("__")
+        && !server.is_empty()
+        && !tool.is_empty()
+    {
+        return Some(format!("mcp__{}__{}", server, tool));
+    }
+
+    Some(name.to_string())
+}
+
+/// Program a shell call runs, e.g. `git` for `["bash", "-lc", "git status"]`.
+fn shell_program(payload: &Value) -> Option<String> {
+    // function_call arguments are a JSON string; local_shell_call has an action.
+    let arguments: Value = match payload.get("arguments") {
+        Some(Value::String(s)) => serde_json::from_str(s).ok()?,
+        Some(other) => other.clone(),
+        None => payload.get("action")?.clone(),
+    };
+
+    let command_line = match arguments.get("command").or_else(|| arguments.get("cmd"))? {
+        Value::Array(argv) => {
+            let argv: Vec<&str> = argv.iter().filter_map(|a| a.as_str()).collect();
+            match argv.as_slice() {
+                // `bash -lc "<script>"`: the script's first program is what ran.
+                [shell, flag, script, ..] if is_shell(shell) && flag.starts_with('-') && flag.contains('c') => {
+                    script.to_string()
+                }
+                _ => argv.join(" "),
+            }
+        }
+        Value::String(s) => s.clone(),
+        _ => return None,
+    };
+
+    command_line
+        .split_whitespace()
+        // Skip leading `VAR=value` assignments.
+        .find(|word| !word.contains('='))
+        .map(|word| word.rsplit('/').next().unwrap_or(word).to_string())
+}
+
+fn is_shell(program: &str) -> bool {
+    matches!(program.rsplit('/').next(), Some("bash" | "sh" | "zsh"))
+}
+
+fn token_info(usage: &Value) -> TokenInfo {
+    let get = |key: &str| usage.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+    TokenInfo {
+        input: get("input_tokens"),
+        output: get("output_tokens"),
+        cached: get("cached_input_tokens"),
+        cache_creation: 0,
+        reasoning: get("reasoning_output_tokens"),
+        total: get("total_tokens"),
+    }
+}
+
+/// Codex records the environment and AGENTS.md instructions as user
+/// messages; they are not prompts the user typed.
+fn is_injected_context(payload: &Value) -> bool {
+    payload.get("content")
+        .and_then(|c| c.as_array())
+        .and_then(|items| items.first())
+        .and_then(|item| item.get("text"))
+        .and_then(|t| t.as_str())
+        .is_some_and(|text| {
+            let text = text.trim_start();
+            text.starts_with("<environment_context>") || text.starts_with("<user_instructions>")
         })
+}
+
+/// Text the user typed, from a message's `input_text` items.
+fn input_text(payload: &Value) -> Option<&str> {
+    payload.get("content")?
+        .as_array()?
+        .iter()
+        .find(|item| item.get("type").and_then(|t| t.as_str()) == Some("input_text"))?
+        .get("text")?
+        .as_str()
+}
+
+/// Outcome of a tool call from its output. Shell outputs carry an exit
+/// code, either as JSON `metadata.exit_code` or an `Exit code: N` header;
+/// other tools only say so when the user declined them.
+fn output_outcome(output: Option<&Value>) -> Option<ToolOutcome> {
+    let text = output?.as_str()?;
+    if text.contains("rejected by user") {
+        return Some(ToolOutcome::Rejected);
+    }
+
+    let exit_code = match serde_json::from_str::<Value>(text) {
+        Ok(json) => json.get("metadata")?.get("exit_code")?.as_i64()?,
+        Err(_) => text
+            .lines()
+            .find_map(|line| line.strip_prefix("Exit code: "))?
+            .trim()
+            .parse()
+            .ok()?,
+    };
+    Some(if exit_code == 0 { ToolOutcome::Success } else { ToolOutcome::Error })
+}
+
+impl AgentScanner for CodexScanner {
+    fn agent_type(&self) -> AgentType {
+        AgentType::Codex
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        vec![self.root.clone()]
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        // `~/.codex` also holds `history.jsonl` and other logs that are not
+        // rollouts; only `sessions/**/rollout-*.jsonl` are.
+        path.starts_with(self.root.join("sessions"))
+            && path.extension().is_some_and(|ext| ext == "jsonl")
+            && path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("rollout-"))
+    }
+
+    /// Parse a rollout, resuming from its checkpoint when it has grown
+    /// since the last scan.
+    fn parse(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        #[cfg(feature = "cache")]
+        if let Some(ref cache) = self.cache {
+            return Ok(vec![parse_rollout_resumable(file_info, AgentType::Codex, cache, self.parser_version())?]);
+        }
+
+        Ok(vec![parse_rollout(file_info, AgentType::Codex)?])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn only_session_rollouts_match() {
+        let scanner = CodexScanner::new("/home/me");
+        let sessions = Path::new("/home/me/.codex/sessions");
+        assert!(scanner.matches(&sessions.join("2025/01/02/rollout-2025-01-02T03-04-05-abc.jsonl")));
+        assert!(scanner.matches(&sessions.join("rollout-2025-01-02T03-04-05-abc.jsonl")));
+        assert!(!scanner.matches(Path::new("/home/me/.codex/history.jsonl")));
+        assert!(!scanner.matches(&sessions.join("2025/01/02/notes.jsonl")));
+        assert!(!scanner.matches(&sessions.join("2025/01/02/rollout-2025-01-02T03-04-05-abc.json")));
+    }
+
+    #[test]
+    fn shell_calls_are_keyed_by_program() {
+        let bash = json!({"type": "function_call", "name": "shell", "arguments": r#"{"command":["bash","-lc","RUST_LOG=debug cargo test"]}"#});
+        let direct = json!({"type": "function_call", "name": "shell", "arguments": r#"{"command":["/usr/bin/git","status"]}"#});
+        let local = json!({"type": "local_shell_call", "action": {"type": "exec", "command": ["ls", "-la"]}});
+
+        assert_eq!(tool_call_name(&bash).as_deref(), Some("shell:cargo"));
+        assert_eq!(tool_call_name(&direct).as_deref(), Some("shell:git"));
+        assert_eq!(tool_call_name(&local).as_deref(), Some("shell:ls"));
+    }
+
+    #[test]
+    fn mcp_and_builtin_tools_are_recognised() {
+        let mcp = json!({"type": "function_call", "name": "github__create_issue", "arguments": "{}"});
+        let patch = json!({"type": "custom_tool_call", "name": "apply_patch", "input": ""});
+        let plan = json!({"type": "function_call", "name": "update_plan", "arguments": "{}"});
+        let message = json!({"type": "message", "role": "assistant"});
+
+        assert_eq!(tool_call_name(&mcp).as_deref(), Some("mcp__github__create_issue"));
+        assert_eq!(tool_call_name(&patch).as_deref(), Some("apply_patch"));
+        assert_eq!(tool_call_name(&plan).as_deref(), Some("update_plan"));
+        assert_eq!(tool_call_name(&message), None);
+    }
+
+    #[test]
+    fn legacy_rollouts_are_read_as_bare_items() {
+        let path = std::env::temp_dir().join(format!("agent-insights-codex-legacy-{}.jsonl", std::process::id()));
+        let lines = [
+            r#"{"id":"legacy-1","timestamp":"2025-04-20T10:00:00Z","instructions":""}"#,
+            r#"{"record_type":"state"}"#,
+            r#"{"type":"message","role":"user","content":[{"type":"input_text","text":"hi"}]}"#,
+            r#"{"type":"function_call","name":"shell","call_id":"c1","arguments":"{\"command\":[\"ls\"]}"}"#,
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let file_info = FileInfo {
+            path: path.clone(),
+            created_at: chrono::Utc::now(),
+            modified_at: chrono::Utc::now(),
+            size: 0,
+        };
+
+        let record = parse_rollout(&file_info, AgentType::Codex).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(record.session_id.as_deref(), Some("legacy-1"));
+        assert_eq!(record.tool_calls, vec!["shell:ls".to_string()]);
+        let format = record.format.unwrap();
+        assert_eq!(format.schema, "rollout-legacy");
+        assert_eq!(format.drift, None);
+    }
+
+    #[test]
+    fn cli_version_is_read_from_session_meta() {
+        let path = std::env::temp_dir().join(format!("agent-insights-codex-version-{}.jsonl", std::process::id()));
+        let parse = |first_line: &str| {
+            std::fs::write(&path, first_line).unwrap();
+            let file_info = FileInfo {
+                path: path.clone(),
+                created_at: chrono::Utc::now(),
+                modified_at: chrono::Utc::now(),
+                size: 0,
+            };
+            parse_rollout(&file_info, AgentType::Codex).unwrap().cli_version
+        };
+
+        let meta = parse(r#"{"timestamp":"2025-04-20T10:00:00Z","type":"session_meta","payload":{"id":"s1","cli_version":"0.46.0"}}"#);
+        let unversioned = parse(r#"{"timestamp":"2025-04-20T10:00:00Z","type":"session_meta","payload":{"id":"s1"}}"#);
+        let legacy = parse(r#"{"id":"legacy-1","timestamp":"2025-04-20T10:00:00Z","instructions":""}"#);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(meta.as_deref(), Some("0.46.0"));
+        assert_eq!(unversioned, None);
+        assert_eq!(legacy, None);
+    }
+
+    #[cfg(feature = "cache")]
+    #[test]
+    fn grown_rollouts_resume_from_checkpoint() {
+        let dir = std::env::temp_dir().join(format!("agent-insights-codex-tail-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rollout-2025-04-20T10-00-00-abc.jsonl");
+        let cache = CacheDb::new(Some(dir.join("cache.db"))).unwrap();
+        let meta = r#"{"timestamp":"2025-04-20T10:00:00Z","type":"session_meta","payload":{"id":"s1","cwd":"/work","cli_version":"0.46.0"}}"#;
+        let turn = |n: u64| {
+            let call = format!(r#"{{"timestamp":"2025-04-20T10:0{n}:00Z","type":"response_item","payload":{{"type":"function_call","name":"shell","call_id":"c{n}","arguments":"{{\"command\":[\"ls\"]}}"}}}}"#);
+            let usage = format!(r#"{{"timestamp":"2025-04-20T10:0{n}:30Z","type":"event_msg","payload":{{"type":"token_count","info":{{"total_token_usage":{{"input_tokens":{},"output_tokens":{},"total_tokens":{}}},"last_token_usage":{{"input_tokens":100,"output_tokens":10,"total_tokens":110}}}}}}}}"#, n * 100, n * 10, n * 110);
+            format!("{}\n{}\n", call, usage)
+        };
+        let parse = |contents: &str| {
+            std::fs::write(&path, contents).unwrap();
+            let file_info = FileInfo {
+                path: path.clone(),
+                created_at: chrono::Utc::now(),
+                modified_at: chrono::Utc::now(),
+                size: contents.len() as u64,
+            };
+            let record = parse_rollout_resumable(&file_info, AgentType::Codex, &cache, 1).unwrap();
+            let full = parse_rollout(&file_info, AgentType::Codex).unwrap();
+            assert_eq!(format!("{:?}", record.events), format!("{:?}", full.events));
+            assert_eq!(format!("{:?}", record.days), format!("{:?}", full.days));
+            cache.cache_record(&record, 1).unwrap();
+            let checkpoint = cache.get_checkpoint(&path.to_string_lossy(), 1).unwrap().unwrap();
+            assert!(!checkpoint.state.contains("shell"));
+            (record.tokens.unwrap().total, record.tool_calls.len(), checkpoint.offset)
+        };
+
+        let first = format!("{}\n{}", meta, turn(1));
+        assert_eq!(parse(&first), (110, 1, first.len() as u64));
+        let grown = format!("{}{}{}", first, turn(2), turn(3));
+        assert_eq!(parse(&grown), (330, 3, grown.len() as u64));
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
@@ -0,0 +1,124 @@
+#[cfg(feature = "cache")]
+use crate::cache::CacheDb;
+use crate::domain::{AgentRecord, AgentType};
+use crate::scanner::FileInfo;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "cache")]
+use std::sync::Arc;
+
+use super::codex::parse_rollout;
+#[cfg(feature = "cache")]
+use super::codex::parse_rollout_resumable;
+use super::AgentScanner;
+
+/// Environment variable overriding the directory Codexia keeps its
+/// rollouts in.
+pub const SESSIONS_ENV: &str = "CODEXIA_SESSIONS";
+
+/// Sessions started from the Codexia desktop app.
+///
+/// Codexia drives Codex and keeps its own rollouts under
+/// `~/.codexia/sessions`, separate from `~/.codex`, so they are counted
+/// as their own agent.
+pub struct CodexiaScanner {
+    root: PathBuf,
+    #[cfg(feature = "cache")]
+    cache: Option<Arc<CacheDb>>,
+}
+
+impl CodexiaScanner {
+    /// Read the rollouts in `CODEXIA_SESSIONS`, or `~/.codexia/sessions`
+    /// when it is unset.
+    pub fn new(home_dir: &str) -> Self {
+        match std::env::var_os(SESSIONS_ENV) {
+            Some(root) => Self::with_root(PathBuf::from(root)),
+            None => Self::with_root(PathBuf::from(home_dir).join(".codexia").join("sessions")),
+        }
+    }
+
+    pub fn with_root(root: PathBuf) -> Self {
+        Self {
+            root,
+            #[cfg(feature = "cache")]
+            cache: None,
+        }
+    }
+
+    /// Create a scanner keeping parse checkpoints in `cache`, as
+    /// [`CodexScanner::with_cache`] does.
+    ///
+    /// [`CodexScanner::with_cache`]: super::CodexScanner::with_cache
+    #[cfg(feature = "cache")]
+    pub fn with_cache(home_dir: &str, cache: Arc<CacheDb>) -> Self {
+        Self { cache: Some(cache), ..Self::new(home_dir) }
+    }
+}
+
+impl AgentScanner for CodexiaScanner {
+    fn agent_type(&self) -> AgentType {
+        AgentType::Codexia
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        vec![self.root.clone()]
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.extension().is_some_and(|ext| ext == "jsonl")
+    }
+
+    fn parse(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        #[cfg(feature = "cache")]
+        if let Some(ref cache) = self.cache {
+            return Ok(vec![parse_rollout_resumable(file_info, AgentType::Codexia, cache, self.parser_version())?]);
+        }
+
+        Ok(vec![parse_rollout(file_info, AgentType::Codexia)?])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sessions_are_read_from_the_configured_root() {
+        let scanner = CodexiaScanner::with_root(PathBuf::from("/data/codexia"));
+        assert_eq!(scanner.roots(), vec![PathBuf::from("/data/codexia")]);
+    }
+
+    #[test]
+    fn rollouts_are_parsed_as_codexia_sessions() {
+        let dir = std::env::temp_dir().join(format!("agent-insights-codexia-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rollout-2025-04-20T10-00-00-abc.jsonl");
+        let lines = [
+            r#"{"timestamp":"2025-04-20T10:00:00Z","type":"session_meta","payload":{"id":"s1","cwd":"/work","cli_version":"0.46.0"}}"#,
+            r#"{"timestamp":"2025-04-20T10:00:05Z","type":"turn_context","payload":{"model":"gpt-5-codex"}}"#,
+            r#"{"timestamp":"2025-04-20T10:01:00Z","type":"response_item","payload":{"type":"function_call","name":"shell","call_id":"c1","arguments":"{\"command\":[\"ls\"]}"}}"#,
+            r#"{"timestamp":"2025-04-20T10:01:30Z","type":"event_msg","payload":{"type":"token_count","info":{"total_token_usage":{"input_tokens":100,"output_tokens":10,"total_tokens":110}}}}"#,
+        ];
+        std::fs::write(&path, lines.join("\n")).unwrap();
+        let file_info = FileInfo {
+            path: path.clone(),
+            created_at: chrono::Utc::now(),
+            modified_at: chrono::Utc::now(),
+            size: 0,
+        };
+
+        let scanner = CodexiaScanner::with_root(dir.clone());
+        assert!(scanner.matches(&path));
+        let records = scanner.parse(&file_info).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.agent_type, AgentType::Codexia);
+        assert_eq!(record.session_id.as_deref(), Some("s1"));
+        assert_eq!(record.cwd.as_deref(), Some("/work"));
+        assert_eq!(record.model.as_deref(), Some("gpt-5-codex"));
+        assert_eq!(record.tool_calls, vec!["shell:ls".to_string()]);
+        assert_eq!(record.tokens.as_ref().unwrap().total, 110);
+    }
+}
@@ -0,0 +1,292 @@
+use crate::domain::{AgentRecord, AgentType, ScanFailure, TokenInfo};
+use crate::scanner::{from_unix, session_title, ActivityRange, FileInfo, FileScanner};
+use anyhow::Result;
+use rusqlite::{params, Connection, OpenFlags};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use super::aider::IGNORED_DIRS;
+use super::AgentScanner;
+
+const DB_FILE: &str = "crush.db";
+
+/// Environment variable listing the workspaces searched for project-local
+/// Crush databases, separated like `PATH`.
+pub const WORKSPACES_ENV: &str = "CRUSH_WORKSPACES";
+
+/// How deep below a workspace root a database may sit: a project up to
+/// three levels down, then `.crush/crush.db`.
+const MAX_DEPTH: usize = 5;
+
+/// Crush (and the Go OpenCode it grew out of) stores sessions in SQLite:
+/// a `sessions` table with per-session token totals and a `messages` table
+/// whose `parts` column is a JSON array of content blocks.
+///
+/// Besides its data directory, Crush can keep a database inside the
+/// project it runs in, as `<project>/.crush/crush.db`. Those are searched
+/// for under the workspaces in `CRUSH_WORKSPACES`.
+pub struct CrushScanner {
+    root: PathBuf,
+    workspaces: Vec<PathBuf>,
+}
+
+impl CrushScanner {
+    pub fn new(home_dir: &str) -> Self {
+        let root = PathBuf::from(home_dir)
+            .join(".local")
+            .join("share")
+            .join("crush");
+        let workspaces = std::env::var_os(WORKSPACES_ENV)
+            .map(|paths| std::env::split_paths(&paths).collect())
+            .unwrap_or_default();
+        Self { root, workspaces }
+    }
+
+    fn parse_database(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        // Crush may be running; never take a write lock on its database.
+        let conn = Connection::open_with_flags(
+            &file_info.path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        read_sessions(&conn, file_info)
+    }
+}
+
+/// One record per row of the `sessions` table in `conn`.
+fn read_sessions(conn: &Connection, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+    // A project-local database lives in `<project>/.crush/crush.db`.
+    let cwd = file_info.path
+        .parent()
+        .filter(|dir| dir.file_name().is_some_and(|n| n == ".crush"))
+        .and_then(|dir| dir.parent())
+        .map(|p| p.to_string_lossy().to_string());
+
+    let mut sessions = conn.prepare(
+        "SELECT id, parent_session_id, prompt_tokens, completion_tokens, created_at, updated_at, title
+         FROM sessions ORDER BY created_at"
+    )?;
+    let mut messages = conn.prepare(
+        "SELECT model, parts FROM messages WHERE session_id = ?1 ORDER BY created_at"
+    )?;
+
+    let rows = sessions.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, Option<i64>>(2)?.unwrap_or(0),
+            row.get::<_, Option<i64>>(3)?.unwrap_or(0),
+            row.get::<_, Option<i64>>(4)?,
+            row.get::<_, Option<i64>>(5)?,
+            row.get::<_, Option<String>>(6)?,
+        ))
+    })?;
+
+    let mut records = Vec::new();
+    for row in rows {
+        let (session_id, parent_session_id, prompt_tokens, completion_tokens, created, updated, title) = row?;
+
+        // Crush writes Unix seconds; some builds wrote milliseconds.
+        let mut activity = ActivityRange::default();
+        for at in [created, updated].into_iter().flatten().filter_map(from_unix) {
+            activity.observe(at);
+        }
+        let (started_at, ended_at) = activity.or_file_times(file_info.created_at, file_info.modified_at);
+
+        let mut model: Option<String> = None;
+        let mut tool_calls: Vec<String> = Vec::new();
+        let mut size = 0u64;
+
+        let message_rows = messages.query_map(params![session_id], |row| {
+            Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?))
+        })?;
+        for message in message_rows {
+            let (message_model, parts) = message?;
+            if model.is_none() {
+                model = message_model.filter(|m| !m.is_empty());
+            }
+            let Some(parts) = parts else {
+                continue;
+            };
+            size += parts.len() as u64;
+            tool_calls.extend(tool_call_names(&parts));
+        }
+
+        let input = prompt_tokens.max(0) as u64;
+        let output = completion_tokens.max(0) as u64;
+        let tokens = if input > 0 || output > 0 {
+            Some(TokenInfo {
+                input,
+                output,
+                cached: 0,
+                cache_creation: 0,
+                reasoning: 0,
+                total: input + output,
+            })
+        } else {
+            None
+        };
+
+        records.push(AgentRecord {
+            agent_type: AgentType::Crush,
+            file_path: file_info.path.to_string_lossy().to_string(),
+            created_at: file_info.created_at,
+            modified_at: file_info.modified_at,
+            started_at,
+            ended_at,
+            file_size: size,
+            session_id: Some(session_id),
+            parent_session_id: parent_session_id.filter(|p| !p.is_empty()),
+            model,
+            cwd: cwd.clone(),
+            title: title.as_deref().and_then(session_title),
+            tokens,
+            tool_calls,
+            days: Vec::new(),
+            events: Vec::new(),
+            format: None,
+            cli_version: None,
+            git_branch: None,
+            git_commit: None,
+            repo_root: None,
+            repo_remote: None,
+            source_missing: false,
+        });
+    }
+
+    Ok(records)
+}
+
+impl AgentScanner for CrushScanner {
+    fn agent_type(&self) -> AgentType {
+        AgentType::Crush
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        std::iter::once(self.root.clone()).chain(self.workspaces.iter().cloned()).collect()
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.file_name().is_some_and(|name| name == DB_FILE)
+    }
+
+    fn files(&self, failures: &mut Vec<ScanFailure>) -> Vec<FileInfo> {
+        let mut files = FileScanner::new(self.root.clone()).walk_matching(|p| self.matches(p), failures);
+        for workspace in &self.workspaces {
+            let scanner = FileScanner::new(workspace.clone())
+                .with_max_depth(MAX_DEPTH)
+                .with_ignored_dirs(IGNORED_DIRS);
+            files.extend(scanner.walk_matching(|p| self.matches(p), failures));
+        }
+        // Writes land in the write-ahead log until SQLite checkpoints it, so
+        // the database file alone can look unchanged.
+        for file in &mut files {
+            let mut wal = file.path.clone().into_os_string();
+            wal.push("-wal");
+            file.touched_with(Path::new(&wal));
+        }
+        files
+    }
+
+    fn parse(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        self.parse_database(file_info)
+    }
+}
+
+/// Names of the `tool_call` parts in a message's `parts` JSON array.
+fn tool_call_names(parts: &str) -> Vec<String> {
+    let Ok(Value::Array(parts)) = serde_json::from_str::<Value>(parts) else {
+        return Vec::new();
+    };
+    parts
+        .iter()
+        .filter(|part| part.get("type").and_then(|t| t.as_str()) == Some("tool_call"))
+        .filter_map(|part| part.get("data").and_then(|d| d.get("name")).and_then(|n| n.as_str()))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn tool_calls_are_read_from_message_parts() {
+        let parts = r#"[
+            {"type": "text", "data": {"text": "Looking"}},
+            {"type": "tool_call", "data": {"id": "1", "name": "view"}},
+            {"type": "tool_call", "data": {"id": "2", "name": "bash"}},
+            {"type": "tool_result", "data": {"tool_call_id": "1"}}
+        ]"#;
+        assert_eq!(tool_call_names(parts), vec!["view", "bash"]);
+        assert!(tool_call_names("not json").is_empty());
+        assert!(tool_call_names(r#"{"type": "tool_call"}"#).is_empty());
+    }
+
+    #[test]
+    fn sessions_carry_their_token_totals_and_tool_calls() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"CREATE TABLE sessions (id TEXT, parent_session_id TEXT, title TEXT,
+                   prompt_tokens INTEGER, completion_tokens INTEGER, created_at INTEGER, updated_at INTEGER);
+               CREATE TABLE messages (session_id TEXT, model TEXT, parts TEXT, created_at INTEGER);
+               INSERT INTO sessions VALUES ('main', NULL, 'Add caching', 1200, 300, 1735700000, 1735700600);
+               INSERT INTO sessions VALUES ('task', 'main', 'Find callers', 0, 0, 1735700100, NULL);
+               INSERT INTO messages VALUES ('main', '', '[{"type":"text","data":{"text":"hi"}}]', 1735700000);
+               INSERT INTO messages VALUES ('main', 'gpt-4.1',
+                   '[{"type":"tool_call","data":{"name":"edit"}},{"type":"tool_call","data":{"name":"bash"}}]', 1735700010);
+               INSERT INTO messages VALUES ('task', 'gpt-4.1-mini', NULL, 1735700100);"#,
+        )
+        .unwrap();
+        let file_info = FileInfo {
+            path: PathBuf::from("/work/app/.crush/crush.db"),
+            created_at: Utc::now(),
+            modified_at: Utc::now(),
+            size: 0,
+        };
+
+        let records = read_sessions(&conn, &file_info).unwrap();
+
+        assert_eq!(records.len(), 2);
+        let (main, task) = (&records[0], &records[1]);
+        assert_eq!(main.session_id.as_deref(), Some("main"));
+        assert_eq!(main.model.as_deref(), Some("gpt-4.1"));
+        assert_eq!(main.cwd.as_deref(), Some("/work/app"));
+        assert_eq!(main.tool_calls, vec!["edit", "bash"]);
+        let tokens = main.tokens.as_ref().unwrap();
+        assert_eq!((tokens.input, tokens.output, tokens.total), (1200, 300, 1500));
+        assert_eq!(main.started_at, from_unix(1735700000).unwrap());
+        assert_eq!(main.ended_at, from_unix(1735700600).unwrap());
+        assert_eq!(task.parent_session_id.as_deref(), Some("main"));
+        assert!(task.tokens.is_none());
+    }
+
+    #[test]
+    fn project_databases_are_found_under_workspaces() {
+        let dir = std::env::temp_dir().join(format!("agent-insights-crush-{}", std::process::id()));
+        let data = dir.join("share").join("crush");
+        let workspace = dir.join("work");
+        let databases = [
+            data.join(DB_FILE),
+            workspace.join("app").join(".crush").join(DB_FILE),
+            workspace.join("a").join("b").join("c").join(".crush").join(DB_FILE),
+            // Too deep, or in a directory never searched
+            workspace.join("a").join("b").join("c").join("d").join(".crush").join(DB_FILE),
+            workspace.join("node_modules").join("pkg").join(".crush").join(DB_FILE),
+        ];
+        for db in &databases {
+            std::fs::create_dir_all(db.parent().unwrap()).unwrap();
+            std::fs::write(db, "").unwrap();
+        }
+        let scanner = CrushScanner { root: data.clone(), workspaces: vec![workspace.clone()] };
+
+        let mut found: Vec<PathBuf> = scanner.files(&mut Vec::new()).into_iter().map(|f| f.path).collect();
+        found.sort();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let mut expected = databases[..3].to_vec();
+        expected.sort();
+        assert_eq!(found, expected);
+        assert_eq!(scanner.roots(), vec![data, workspace]);
+    }
+}
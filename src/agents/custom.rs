@@ -0,0 +1,502 @@
+use crate::domain::{AgentEvent, AgentRecord, AgentType, EventKind, LogFormat, TokenInfo};
+use crate::scanner::{parse_timestamp, session_title, ActivityRange, DailyActivity, FileInfo, JsonlReader};
+use anyhow::{Context, Result};
+use globset::{Glob, GlobMatcher};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::AgentScanner;
+
+/// File, relative to the home directory, describing custom agents.
+pub const CONFIG_FILE: &str = ".agent-insights/agents.json";
+
+/// Top-level shape of `~/.agent-insights/agents.json`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CustomAgentsConfig {
+    #[serde(default)]
+    pub agents: Vec<CustomAgentDefinition>,
+}
+
+impl CustomAgentsConfig {
+    /// Load the config from the home directory. A missing file is an empty
+    /// config.
+    pub fn load(home_dir: &str) -> Result<Self> {
+        let path = PathBuf::from(home_dir).join(CONFIG_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        Self::load_from(&path)
+    }
+
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid custom agent config {:?}", path))
+    }
+}
+
+/// Description of an agent whose sessions can be read without a dedicated
+/// parser.
+///
+/// ```json
+/// {
+///   "name": "Forge",
+///   "root": "~/.forge/sessions",
+///   "glob": "**/*.jsonl",
+///   "format": "jsonl",
+///   "tokens": "cumulative",
+///   "fields": {
+///     "session_id": { "path": "/payload/id", "when": { "/type": "session_meta" } },
+///     "model": "/payload/model",
+///     "cwd": "/payload/cwd",
+///     "title": { "path": "/payload/content/0/text", "when": { "/payload/role": "user" } },
+///     "timestamp": "/timestamp",
+///     "input_tokens": "/payload/info/total_token_usage/input_tokens",
+///     "output_tokens": "/payload/info/total_token_usage/output_tokens",
+///     "tool_name": { "path": "/payload/name", "when": { "/type": "response_item" } }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomAgentDefinition {
+    /// Display name; also the API id once lowercased.
+    pub name: String,
+    /// Directory searched for session files. A leading `~` is the home directory.
+    pub root: String,
+    /// Pattern matched against paths relative to `root`.
+    #[serde(default = "default_glob")]
+    pub glob: String,
+    pub format: CustomFormat,
+    /// For `json` files, a pointer to the array of messages inside the
+    /// document. Fields are looked up on the document and on every message.
+    #[serde(default)]
+    pub items: Option<String>,
+    #[serde(default)]
+    pub tokens: TokenMode,
+    /// How a session's total is counted when no `total_tokens` field is
+    /// mapped.
+    #[serde(default)]
+    pub total: TokenTotal,
+    #[serde(default)]
+    pub fields: CustomFields,
+}
+
+fn default_glob() -> String {
+    "**/*".to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CustomFormat {
+    /// One JSON document per file.
+    Json,
+    /// One JSON value per line.
+    Jsonl,
+}
+
+/// How token fields found on several lines combine.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenMode {
+    /// Each line carries the usage of one message; values are added up.
+    #[default]
+    Sum,
+    /// Each line carries running totals (like Codex `token_count`); the
+    /// last value wins.
+    Cumulative,
+}
+
+/// Which token counts make up the total when the log has no total of its
+/// own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenTotal {
+    /// Input plus output, for logs (like OpenAI's) whose input already
+    /// includes cached tokens and whose output includes reasoning.
+    #[default]
+    InputOutput,
+    /// Every count added up, for logs (like Anthropic's) that report cache
+    /// reads and writes apart from input.
+    AllFields,
+}
+
+impl TokenTotal {
+    fn of(self, usage: &TokenInfo) -> u64 {
+        match self {
+            TokenTotal::InputOutput => usage.input + usage.output,
+            TokenTotal::AllFields => {
+                usage.input + usage.output + usage.cached + usage.cache_creation + usage.reasoning
+            }
+        }
+    }
+}
+
+/// JSON pointers (RFC 6901) to the values making up an [`AgentRecord`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CustomFields {
+    pub session_id: Option<FieldPath>,
+    pub model: Option<FieldPath>,
+    pub cwd: Option<FieldPath>,
+    /// Session title or first prompt; the first value found is used.
+    pub title: Option<FieldPath>,
+    /// RFC 3339 string or Unix epoch (seconds or milliseconds).
+    pub timestamp: Option<FieldPath>,
+    pub input_tokens: Option<FieldPath>,
+    pub output_tokens: Option<FieldPath>,
+    pub cached_tokens: Option<FieldPath>,
+    pub cache_creation_tokens: Option<FieldPath>,
+    pub reasoning_tokens: Option<FieldPath>,
+    pub total_tokens: Option<FieldPath>,
+    pub tool_name: Option<FieldPath>,
+}
+
+/// A pointer, optionally only applied to values where other pointers hold
+/// given values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FieldPath {
+    Pointer(String),
+    Conditional {
+        path: String,
+        #[serde(default)]
+        when: HashMap<String, Value>,
+    },
+}
+
+impl FieldPath {
+    fn lookup<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        match self {
+            FieldPath::Pointer(path) => value.pointer(path),
+            FieldPath::Conditional { path, when } => {
+                let applies = when.iter().all(|(p, expected)| value.pointer(p) == Some(expected));
+                if applies { value.pointer(path) } else { None }
+            }
+        }
+    }
+
+    fn str<'a>(&self, value: &'a Value) -> Option<&'a str> {
+        self.lookup(value).and_then(|v| v.as_str())
+    }
+
+    fn u64(&self, value: &Value) -> Option<u64> {
+        self.lookup(value).and_then(|v| v.as_u64())
+    }
+}
+
+/// Scanner interpreting a [`CustomAgentDefinition`].
+pub struct CustomScanner {
+    definition: CustomAgentDefinition,
+    root: PathBuf,
+    matcher: GlobMatcher,
+}
+
+impl CustomScanner {
+    pub fn new(definition: CustomAgentDefinition, home_dir: &str) -> Result<Self> {
+        let root = match definition.root.strip_prefix("~/") {
+            Some(rest) => PathBuf::from(home_dir).join(rest),
+            None if definition.root == "~" => PathBuf::from(home_dir),
+            None => PathBuf::from(&definition.root),
+        };
+        let matcher = Glob::new(&definition.glob)
+            .with_context(|| format!("Invalid glob for custom agent {}", definition.name))?
+            .compile_matcher();
+        Ok(Self { definition, root, matcher })
+    }
+
+    /// Scanners for every agent in the home directory's config. Invalid
+    /// definitions are logged and skipped.
+    pub fn load_all(home_dir: &str) -> Vec<Self> {
+        let config = match CustomAgentsConfig::load(home_dir) {
+            Ok(config) => config,
+            Err(e) => {
+                tracing::warn!("Failed to load custom agents: {}", e);
+                return Vec::new();
+            }
+        };
+        config
+            .agents
+            .into_iter()
+            .filter_map(|definition| match Self::new(definition, home_dir) {
+                Ok(scanner) => Some(scanner),
+                Err(e) => {
+                    tracing::warn!("Skipping custom agent: {}", e);
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn parse_file(&self, file_info: &FileInfo) -> Result<AgentRecord> {
+        let mut reader = None;
+        let values: Box<dyn Iterator<Item = Value> + '_> = match self.definition.format {
+            CustomFormat::Jsonl => Box::new(reader.insert(JsonlReader::open(&file_info.path)?).by_ref()),
+            CustomFormat::Json => {
+                let content = fs::read_to_string(&file_info.path)?;
+                let document: Value = serde_json::from_str(&content)?;
+                let items = self.definition.items.as_deref()
+                    .and_then(|p| document.pointer(p))
+                    .and_then(|v| v.as_array())
+                    .cloned()
+                    .unwrap_or_default();
+                Box::new(std::iter::once(document).chain(items))
+            }
+        };
+
+        let fields = &self.definition.fields;
+        let mut session_id: Option<String> = None;
+        let mut model: Option<String> = None;
+        let mut cwd: Option<String> = None;
+        let mut title: Option<String> = None;
+        let mut activity = ActivityRange::default();
+        let mut daily = DailyActivity::default();
+        let mut events: Vec<AgentEvent> = Vec::new();
+        let mut usage = TokenInfo {
+            input: 0,
+            output: 0,
+            cached: 0,
+            cache_creation: 0,
+            reasoning: 0,
+            total: 0,
+        };
+        let mut tool_calls: Vec<String> = Vec::new();
+
+        let first_str = |field: &Option<FieldPath>, value: &Value, slot: &mut Option<String>| {
+            if slot.is_none()
+                && let Some(s) = field.as_ref().and_then(|f| f.str(value))
+            {
+                *slot = Some(s.to_string());
+            }
+        };
+
+        for value in values {
+            let value = &value;
+            first_str(&fields.session_id, value, &mut session_id);
+            first_str(&fields.model, value, &mut model);
+            first_str(&fields.cwd, value, &mut cwd);
+            first_str(&fields.title, value, &mut title);
+
+            let timestamp = fields.timestamp.as_ref().and_then(|f| f.lookup(value));
+            activity.observe_value(timestamp);
+            daily.observe_value(timestamp);
+            let at = timestamp.and_then(parse_timestamp);
+
+            let mode = self.definition.tokens;
+            let before = usage.clone();
+            let mut found = false;
+            let mut add = |field: &Option<FieldPath>, slot: &mut u64| {
+                if let Some(n) = field.as_ref().and_then(|f| f.u64(value)) {
+                    found = true;
+                    *slot = match mode {
+                        TokenMode::Sum => slot.saturating_add(n),
+                        TokenMode::Cumulative => n,
+                    };
+                }
+            };
+            add(&fields.input_tokens, &mut usage.input);
+            add(&fields.output_tokens, &mut usage.output);
+            add(&fields.cached_tokens, &mut usage.cached);
+            add(&fields.cache_creation_tokens, &mut usage.cache_creation);
+            add(&fields.reasoning_tokens, &mut usage.reasoning);
+            add(&fields.total_tokens, &mut usage.total);
+
+            // What this line added; running totals only count their growth.
+            if found {
+                let mut line = TokenInfo {
+                    input: usage.input.saturating_sub(before.input),
+                    output: usage.output.saturating_sub(before.output),
+                    cached: usage.cached.saturating_sub(before.cached),
+                    cache_creation: usage.cache_creation.saturating_sub(before.cache_creation),
+                    reasoning: usage.reasoning.saturating_sub(before.reasoning),
+                    total: usage.total.saturating_sub(before.total),
+                };
+                if fields.total_tokens.is_none() {
+                    line.total = self.definition.total.of(&line);
+                }
+                daily.today().tokens += line.total;
+                events.push(
+                    AgentEvent::new(EventKind::TokenUsage, at)
+                        .with_model(model.as_deref())
+                        .with_usage(Some(line)),
+                );
+            }
+
+            if let Some(name) = fields.tool_name.as_ref().and_then(|f| f.str(value)) {
+                daily.today().tool_calls += 1;
+                events.push(
+                    AgentEvent::new(EventKind::ToolCall, at)
+                        .with_model(model.as_deref())
+                        .with_tool(Some(name), None),
+                );
+                tool_calls.push(name.to_string());
+            }
+        }
+
+        let mut format = LogFormat::new(match self.definition.format {
+            CustomFormat::Json => "json",
+            CustomFormat::Jsonl => "jsonl",
+        });
+        if let Some(ref mut reader) = reader {
+            reader.finish()?;
+            format.invalid_lines = reader.invalid_lines();
+        }
+
+        let tokens = if usage.input > 0 || usage.output > 0 {
+            if fields.total_tokens.is_none() {
+                usage.total = self.definition.total.of(&usage);
+            }
+            Some(usage)
+        } else {
+            None
+        };
+
+        let (started_at, ended_at) = activity.or_file_times(file_info.created_at, file_info.modified_at);
+
+        Ok(AgentRecord {
+            agent_type: self.agent_type(),
+            file_path: file_info.path.to_string_lossy().to_string(),
+            created_at: file_info.created_at,
+            modified_at: file_info.modified_at,
+            started_at,
+            ended_at,
+            file_size: file_info.size,
+            session_id,
+            parent_session_id: None,
+            model,
+            cwd,
+            title: title.as_deref().and_then(session_title),
+            tokens,
+            tool_calls,
+            days: daily.into_days(),
+            events,
+            format: Some(format),
+            cli_version: None,
+            git_branch: None,
+            git_commit: None,
+            repo_root: None,
+            repo_remote: None,
+            source_missing: false,
+        })
+    }
+}
+
+impl AgentScanner for CustomScanner {
+    fn agent_type(&self) -> AgentType {
+        AgentType::Custom(self.definition.name.clone())
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        vec![self.root.clone()]
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.strip_prefix(&self.root)
+            .is_ok_and(|relative| self.matcher.is_match(relative))
+    }
+
+    fn parse(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        Ok(vec![self.parse_file(file_info)?])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn scanner(definition: Value) -> CustomScanner {
+        CustomScanner::new(serde_json::from_value(definition).unwrap(), "/home/me").unwrap()
+    }
+
+    fn parse(scanner: &CustomScanner, name: &str, content: &str) -> AgentRecord {
+        let path = std::env::temp_dir().join(format!("agent-insights-custom-{}-{}", std::process::id(), name));
+        fs::write(&path, content).unwrap();
+        let file_info = FileInfo { path: path.clone(), created_at: Utc::now(), modified_at: Utc::now(), size: 0 };
+        let record = scanner.parse_file(&file_info).unwrap();
+        fs::remove_file(path).unwrap();
+        record
+    }
+
+    #[test]
+    fn glob_is_matched_below_the_expanded_root() {
+        let scanner = scanner(serde_json::json!({
+            "name": "Forge", "root": "~/.forge", "glob": "sessions/**/*.jsonl", "format": "jsonl"
+        }));
+        assert_eq!(scanner.roots(), vec![PathBuf::from("/home/me/.forge")]);
+        assert!(scanner.matches(Path::new("/home/me/.forge/sessions/2025/a.jsonl")));
+        assert!(!scanner.matches(Path::new("/home/me/.forge/sessions/a.json")));
+        assert!(!scanner.matches(Path::new("/home/me/.forge/logs/a.jsonl")));
+        assert!(!scanner.matches(Path::new("/elsewhere/sessions/a.jsonl")));
+    }
+
+    #[test]
+    fn cumulative_jsonl_fields_are_mapped_by_pointer() {
+        let scanner = scanner(serde_json::json!({
+            "name": "Forge",
+            "root": "/logs",
+            "format": "jsonl",
+            "tokens": "cumulative",
+            "fields": {
+                "session_id": { "path": "/payload/id", "when": { "/type": "session_meta" } },
+                "model": "/payload/model",
+                "title": { "path": "/payload/text", "when": { "/payload/role": "user" } },
+                "timestamp": "/timestamp",
+                "input_tokens": "/payload/usage/input",
+                "output_tokens": "/payload/usage/output",
+                "cached_tokens": "/payload/usage/cached",
+                "tool_name": { "path": "/payload/name", "when": { "/type": "tool" } }
+            }
+        }));
+        let lines = [
+            r#"{"timestamp":"2025-03-01T23:50:00Z","type":"session_meta","payload":{"id":"f1","model":"gpt-5"}}"#,
+            r#"{"timestamp":"2025-03-01T23:51:00Z","type":"message","payload":{"role":"user","text":"Rename the module"}}"#,
+            r#"{"timestamp":"2025-03-01T23:52:00Z","type":"usage","payload":{"usage":{"input":100,"output":10,"cached":80}}}"#,
+            r#"{"timestamp":"2025-03-02T00:05:00Z","type":"tool","payload":{"name":"edit","id":"not-a-session"}}"#,
+            r#"{"timestamp":"2025-03-02T00:06:00Z","type":"usage","payload":{"usage":{"input":250,"output":30,"cached":200}}}"#,
+        ];
+
+        let record = parse(&scanner, "cumulative.jsonl", &lines.join("\n"));
+
+        assert_eq!(record.agent_type, AgentType::Custom("Forge".to_string()));
+        assert_eq!(record.session_id.as_deref(), Some("f1"));
+        assert_eq!(record.model.as_deref(), Some("gpt-5"));
+        assert_eq!(record.title.as_deref(), Some("Rename the module"));
+        assert_eq!(record.tool_calls, vec!["edit"]);
+        // Cached tokens are part of input, so not added to the total again
+        let tokens = record.tokens.unwrap();
+        assert_eq!((tokens.input, tokens.output, tokens.cached, tokens.total), (250, 30, 200, 280));
+        let days: Vec<_> = record.days.iter().map(|d| (d.date.to_string(), d.tokens, d.tool_calls)).collect();
+        assert_eq!(days, vec![("2025-03-01".to_string(), 110, 0), ("2025-03-02".to_string(), 170, 1)]);
+        let kinds: Vec<_> = record.events.iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![EventKind::TokenUsage, EventKind::ToolCall, EventKind::TokenUsage]);
+    }
+
+    #[test]
+    fn json_items_are_summed_and_totalled_as_configured() {
+        let definition = |total: &str| serde_json::json!({
+            "name": "Notes",
+            "root": "/logs",
+            "format": "json",
+            "items": "/messages",
+            "total": total,
+            "fields": {
+                "session_id": "/id",
+                "input_tokens": "/usage/input",
+                "output_tokens": "/usage/output",
+                "cache_creation_tokens": "/usage/cache_write"
+            }
+        });
+        let document = r#"{"id":"n1","messages":[
+            {"usage":{"input":5,"output":7,"cache_write":100}},
+            {"usage":{"input":1,"output":2}}
+        ]}"#;
+
+        let default = parse(&scanner(definition("input_output")), "default.json", document).tokens.unwrap();
+        let all = parse(&scanner(definition("all_fields")), "all.json", document).tokens.unwrap();
+
+        assert_eq!((default.input, default.output, default.cache_creation, default.total), (6, 9, 100, 15));
+        assert_eq!(all.total, 115);
+    }
+}
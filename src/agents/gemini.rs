@@ -1,12 +1,18 @@
-use crate::domain::{AgentRecord, AgentType, TokenInfo};
-use crate::scanner::{FileInfo, FileScanner};
+use crate::domain::{AgentEvent, AgentRecord, AgentType, EventKind, LogFormat, TokenInfo, ToolOutcome};
+use crate::scanner::{parse_timestamp, session_title, ActivityRange, DailyActivity, FileInfo, LossyReader};
 use anyhow::Result;
 use serde_json::Value;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+use super::AgentScanner;
+
+/// Newest major Gemini CLI version whose sessions this parser was checked
+/// against.
+const NEWEST_KNOWN_MAJOR: u64 = 0;
 
 pub struct GeminiScanner {
-    scanner: FileScanner,
+    root: PathBuf,
 }
 
 impl GeminiScanner {
@@ -14,29 +20,26 @@ impl GeminiScanner {
         let root = PathBuf::from(home_dir)
             .join(".gemini")
             .join("tmp");
-        Self {
-            scanner: FileScanner::new(root),
-        }
+        Self { root }
     }
 
-    pub fn scan(&self) -> Result<Vec<AgentRecord>> {
-        let files = self.scanner.scan_json_files()?;
-        let mut records = Vec::new();
+    fn parse_chat_file(&self, file_info: &FileInfo) -> Result<Option<AgentRecord>> {
+        let json: Value = serde_json::from_reader(LossyReader::open(&file_info.path)?)?;
 
-        for file_info in files {
-            if file_info.path.to_string_lossy().contains("/chats/") {
-                if let Ok(record) = self.parse_chat_file(&file_info) {
-                    records.push(record);
-                }
-            }
+        // `/chat save` checkpoints sit beside sessions as bare `{role, parts}`
+        // arrays. They repeat a session's conversation without timestamps or
+        // usage, so they are recognised but contribute no record.
+        match &json {
+            Value::Array(turns) if turns.iter().all(|turn| turn.get("role").is_some()) => return Ok(None),
+            Value::Object(_) => {}
+            _ => anyhow::bail!("unrecognised chat file: neither a session nor a checkpoint"),
         }
 
-        Ok(records)
-    }
-
-    fn parse_chat_file(&self, file_info: &FileInfo) -> Result<AgentRecord> {
-        let content = fs::read_to_string(&file_info.path)?;
-        let json: Value = serde_json::from_str(&content)?;
+        // Only some releases stamp the session with the CLI version
+        let cli_version = json.get("version")
+            .or_else(|| json.get("cliVersion"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
 
         let session_id = json.get("sessionId")
             .or_else(|| json.get("session_id"))
@@ -53,20 +56,49 @@ impl GeminiScanner {
             .map(|s| s.trim().to_string());
 
         let mut model: Option<String> = None;
+        let mut first_prompt: Option<String> = None;
         let mut total_input = 0u64;
         let mut total_output = 0u64;
         let mut total_cached = 0u64;
         let mut total_reasoning = 0u64;
         let mut total_tokens = 0u64;
         let mut tool_calls: Vec<String> = Vec::new();
+        let mut activity = ActivityRange::default();
+        activity.observe_value(json.get("startTime"));
+        activity.observe_value(json.get("lastUpdated"));
+        let mut daily = DailyActivity::default();
+        daily.observe_value(json.get("startTime"));
+        let mut events: Vec<AgentEvent> = Vec::new();
 
         if let Some(messages) = json.get("messages").and_then(|v| v.as_array()) {
             for msg in messages {
-                // Extract model from first message that has it
-                if model.is_none() {
-                    if let Some(m) = msg.get("model").and_then(|v| v.as_str()) {
-                        model = Some(m.to_string());
+                activity.observe_value(msg.get("timestamp"));
+                daily.observe_value(msg.get("timestamp"));
+                daily.today().messages += 1;
+
+                let at = msg.get("timestamp").and_then(parse_timestamp);
+                let message_model = msg.get("model").and_then(|v| v.as_str());
+                match msg.get("type").and_then(|t| t.as_str()) {
+                    Some("user") => {
+                        events.push(AgentEvent::new(EventKind::UserPrompt, at));
+                        if first_prompt.is_none() {
+                            first_prompt = message_text(msg).and_then(session_title);
+                        }
                     }
+                    Some("gemini") => events.push(
+                        AgentEvent::new(EventKind::AssistantMessage, at)
+                            .with_model(message_model)
+                            .with_message_id(msg.get("id").and_then(|v| v.as_str()))
+                            .with_usage(msg.get("tokens").map(token_info)),
+                    ),
+                    _ => {}
+                }
+
+                // Extract model from first message that has it
+                if model.is_none()
+                    && let Some(m) = msg.get("model").and_then(|v| v.as_str())
+                {
+                    model = Some(m.to_string());
                 }
 
                 // Extract token usage from tokens field
@@ -85,14 +117,38 @@ impl GeminiScanner {
                     }
                     if let Some(total) = tokens.get("total").and_then(|v| v.as_u64()) {
                         total_tokens = total_tokens.saturating_add(total);
+                        daily.today().tokens += total;
                     }
                 }
 
                 // Extract tool calls
                 if let Some(tool_calls_array) = msg.get("toolCalls").and_then(|v| v.as_array()) {
                     for tool_call in tool_calls_array {
+                        let call_id = tool_call.get("id").and_then(|v| v.as_str());
+                        let call_at = tool_call.get("timestamp").and_then(parse_timestamp).or(at);
                         if let Some(name) = tool_call.get("name").and_then(|n| n.as_str()) {
                             tool_calls.push(name.to_string());
+                            daily.today().tool_calls += 1;
+                            events.push(
+                                AgentEvent::new(EventKind::ToolCall, call_at)
+                                    .with_model(message_model)
+                                    .with_tool(Some(name), call_id),
+                            );
+                        }
+                        // Calls are stored together with their outcome but no
+                        // completion time, so their duration is unknown.
+                        if let Some(status) = tool_call.get("status").and_then(|v| v.as_str()) {
+                            let outcome = match status {
+                                "success" => Some(ToolOutcome::Success),
+                                "error" => Some(ToolOutcome::Error),
+                                "cancelled" => Some(ToolOutcome::Rejected),
+                                _ => None,
+                            };
+                            events.push(
+                                AgentEvent::new(EventKind::ToolResult, None)
+                                    .with_tool(None, call_id)
+                                    .with_outcome(outcome),
+                            );
                         }
                     }
                 }
@@ -112,17 +168,214 @@ impl GeminiScanner {
             None
         };
 
-        Ok(AgentRecord {
+        let mut format = LogFormat::new("session");
+        format.check_major("Gemini CLI", cli_version.as_deref(), NEWEST_KNOWN_MAJOR);
+        match json.get("messages") {
+            Some(Value::Array(messages)) if messages.iter().all(|m| m.get("type").is_some_and(Value::is_string)) => {}
+            Some(Value::Array(_)) => format.flag("session messages without a type"),
+            Some(_) => format.flag("session messages are not an array"),
+            None => format.flag("session has no messages array"),
+        }
+        if tokens.is_none() && events.iter().any(|e| e.kind == EventKind::AssistantMessage) {
+            format.flag("gemini messages carry no token counts");
+        }
+
+        let (started_at, ended_at) = activity.or_file_times(file_info.created_at, file_info.modified_at);
+
+        Ok(Some(AgentRecord {
             agent_type: AgentType::Gemini,
             file_path: file_info.path.to_string_lossy().to_string(),
             created_at: file_info.created_at,
             modified_at: file_info.modified_at,
+            started_at,
+            ended_at,
             file_size: file_info.size,
             session_id,
+            parent_session_id: None,
             model,
             cwd,
+            // Newer releases store a generated summary with the session
+            title: json.get("summary").and_then(|v| v.as_str()).and_then(session_title).or(first_prompt),
             tokens,
             tool_calls,
-        })
+            days: daily.into_days(),
+            events,
+            format: Some(format),
+            cli_version,
+            git_branch: None,
+            git_commit: None,
+            repo_root: None,
+            repo_remote: None,
+            source_missing: false,
+        }))
+    }
+}
+
+/// A message's text: a plain string, or the text of its parts.
+fn message_text(msg: &Value) -> Option<&str> {
+    match msg.get("content")? {
+        Value::String(text) => Some(text),
+        Value::Array(parts) => parts.iter().find_map(|p| p.get("text")?.as_str()),
+        _ => None,
+    }
+}
+
+fn token_info(tokens: &Value) -> TokenInfo {
+    let get = |key: &str| tokens.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+    TokenInfo {
+        input: get("input"),
+        output: get("output"),
+        cached: get("cached"),
+        cache_creation: 0,
+        reasoning: get("thoughts"),
+        total: get("total"),
+    }
+}
+
+impl AgentScanner for GeminiScanner {
+    fn agent_type(&self) -> AgentType {
+        AgentType::Gemini
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        vec![self.root.clone()]
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        path.extension().is_some_and(|ext| ext == "json")
+            && path.to_string_lossy().contains("/chats/")
+    }
+
+    fn parse(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        Ok(self.parse_chat_file(file_info)?.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use serde_json::json;
+
+    fn parse(name: &str, session: &Value) -> Option<AgentRecord> {
+        let dir = std::env::temp_dir()
+            .join(format!("agent-insights-gemini-{}", std::process::id()))
+            .join("chats");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, session.to_string()).unwrap();
+
+        let file_info = FileInfo {
+            path: path.clone(),
+            created_at: Utc::now(),
+            modified_at: Utc::now(),
+            size: 0,
+        };
+        let record = GeminiScanner::new("/nonexistent").parse_chat_file(&file_info).unwrap();
+        fs::remove_file(path).unwrap();
+        record
+    }
+
+    #[test]
+    fn cli_version_is_read_from_either_key() {
+        let version = |session: Value| parse("session-version.json", &session).unwrap().cli_version;
+
+        assert_eq!(version(json!({"version": "0.9.0", "messages": []})).as_deref(), Some("0.9.0"));
+        assert_eq!(version(json!({"cliVersion": "0.10.1", "messages": []})).as_deref(), Some("0.10.1"));
+        assert_eq!(version(json!({"sessionId": "s1", "messages": []})), None);
+    }
+
+    #[test]
+    fn session_times_come_from_the_session_and_its_messages() {
+        let record = parse("session-times.json", &json!({
+            "sessionId": "s1",
+            "startTime": "2025-05-01T09:00:00Z",
+            "lastUpdated": "2025-05-01T09:30:00Z",
+            "messages": [
+                {"type": "user", "timestamp": "2025-05-01T09:00:05Z", "content": "hi"},
+                {"type": "gemini", "timestamp": "2025-05-01T09:45:00Z", "content": "hello"}
+            ]
+        })).unwrap();
+
+        assert_eq!(record.started_at, "2025-05-01T09:00:00Z".parse::<DateTime<Utc>>().unwrap());
+        // A message logged after lastUpdated still extends the session
+        assert_eq!(record.ended_at, "2025-05-01T09:45:00Z".parse::<DateTime<Utc>>().unwrap());
+    }
+
+    #[test]
+    fn sessions_without_times_fall_back_to_the_file() {
+        let record = parse("session-untimed.json", &json!({"sessionId": "s1", "messages": []})).unwrap();
+        assert_eq!((record.started_at, record.ended_at), (record.created_at, record.modified_at));
+    }
+
+    #[test]
+    fn invalid_utf8_is_replaced_rather_than_failing_the_session() {
+        let dir = std::env::temp_dir().join(format!("agent-insights-gemini-utf8-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("session-utf8.json");
+        let mut content = br#"{"sessionId": "s1", "messages": [{"type": "user", "content": "fix "#.to_vec();
+        content.push(0xff);
+        content.extend_from_slice(br#"it"}]}"#);
+        fs::write(&path, content).unwrap();
+        let file_info = FileInfo { path, created_at: Utc::now(), modified_at: Utc::now(), size: 0 };
+
+        let record = GeminiScanner::new("/nonexistent").parse_chat_file(&file_info).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(record.unwrap().title.as_deref(), Some("fix \u{fffd}it"));
+    }
+
+    #[test]
+    fn newer_major_versions_and_unexpected_shapes_are_flagged() {
+        let drift = |session: Value| parse("session-drift.json", &session).unwrap().format.unwrap().drift;
+
+        assert_eq!(drift(json!({"version": "0.9.0", "messages": [{"type": "user", "content": "hi"}]})), None);
+        assert_eq!(
+            drift(json!({"version": "1.0.0", "messages": []})).as_deref(),
+            Some("Gemini CLI 1.0.0 is newer than the formats this parser knows (up to 0.x)")
+        );
+        assert_eq!(drift(json!({"sessionId": "s1"})).as_deref(), Some("session has no messages array"));
+        assert_eq!(drift(json!({"messages": {"m1": {}}})).as_deref(), Some("session messages are not an array"));
+        assert_eq!(drift(json!({"messages": [{"role": "user"}]})).as_deref(), Some("session messages without a type"));
+    }
+
+    #[test]
+    fn chat_save_checkpoints_are_skipped() {
+        let checkpoint = json!([
+            {"role": "user", "parts": [{"text": "hi"}]},
+            {"role": "model", "parts": [{"text": "hello"}]}
+        ]);
+        assert!(parse("checkpoint-mine.json", &checkpoint).is_none());
+    }
+
+    #[test]
+    fn tool_statuses_become_outcomes() {
+        let record = parse("session-tools.json", &json!({
+            "sessionId": "s1",
+            "messages": [{
+                "type": "gemini",
+                "timestamp": "2025-05-01T09:00:00Z",
+                "toolCalls": [
+                    {"id": "t1", "name": "read_file", "status": "success"},
+                    {"id": "t2", "name": "run_shell_command", "status": "error"},
+                    {"id": "t3", "name": "write_file", "status": "cancelled"},
+                    {"id": "t4", "name": "web_fetch", "status": "executing"},
+                    {"id": "t5", "name": "glob"}
+                ]
+            }]
+        })).unwrap();
+
+        let outcomes: Vec<_> = crate::domain::tool_invocations(&record.events)
+            .into_iter()
+            .map(|call| (call.tool_name, call.outcome))
+            .collect();
+        assert_eq!(outcomes, vec![
+            ("read_file".to_string(), Some(ToolOutcome::Success)),
+            ("run_shell_command".to_string(), Some(ToolOutcome::Error)),
+            ("write_file".to_string(), Some(ToolOutcome::Rejected)),
+            ("web_fetch".to_string(), None),
+            ("glob".to_string(), None),
+        ]);
+        assert_eq!(record.tool_calls.len(), 5);
     }
 }
@@ -1,7 +1,27 @@
+pub mod aider;
+#[cfg(feature = "cache")]
+pub mod cached;
+#[cfg(feature = "cache")]
+mod checkpoint;
 pub mod claude;
 pub mod codex;
+pub mod codexia;
+#[cfg(feature = "cache")]
+pub mod crush;
+pub mod custom;
 pub mod gemini;
+pub mod opencode;
+pub mod registry;
 
+pub use aider::AiderScanner;
+#[cfg(feature = "cache")]
+pub use cached::CachedScanner;
 pub use claude::ClaudeScanner;
 pub use codex::CodexScanner;
+pub use codexia::CodexiaScanner;
+#[cfg(feature = "cache")]
+pub use crush::CrushScanner;
+pub use custom::CustomScanner;
 pub use gemini::GeminiScanner;
+pub use opencode::OpenCodeScanner;
+pub use registry::{AgentScanner, Scan, ScanProgress, ScannerRegistry};
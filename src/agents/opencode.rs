@@ -0,0 +1,314 @@
+use crate::domain::{AgentRecord, AgentType, ScanFailure, TokenInfo};
+use crate::scanner::{from_unix, session_title, ActivityRange, FileInfo, FileScanner};
+use anyhow::Result;
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::AgentScanner;
+
+/// OpenCode keeps one JSON file per session, message and message part:
+///
+/// ```text
+/// storage/session/<projectID>/<sessionID>.json
+/// storage/message/<sessionID>/<messageID>.json
+/// storage/part/<messageID>/<partID>.json
+/// ```
+///
+/// Older releases nest everything under `storage/session/` instead
+/// (`session/info/`, `session/message/<sessionID>/`,
+/// `session/part/<sessionID>/<messageID>/`); both layouts are read.
+pub struct OpenCodeScanner {
+    root: PathBuf,
+}
+
+impl OpenCodeScanner {
+    pub fn new(home_dir: &str) -> Self {
+        let root = PathBuf::from(home_dir)
+            .join(".local")
+            .join("share")
+            .join("opencode")
+            .join("storage");
+        Self { root }
+    }
+
+    fn parse_session_file(&self, file_info: &FileInfo) -> Result<AgentRecord> {
+        let content = fs::read_to_string(&file_info.path)?;
+        let session: Value = serde_json::from_str(&content)?;
+
+        let session_id = session.get("id")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+        // Sessions started by the task tool point at the session that spawned them.
+        let parent_session_id = session.get("parentID")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+        let cwd = session.get("directory")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+        // Session and message `time` objects hold Unix milliseconds.
+        let mut activity = ActivityRange::default();
+        let mut observe_times = |time: Option<&Value>| {
+            for key in ["created", "updated", "completed"] {
+                if let Some(at) = time.and_then(|t| t.get(key)).and_then(|v| v.as_i64()).and_then(from_unix) {
+                    activity.observe(at);
+                }
+            }
+        };
+        observe_times(session.get("time"));
+
+        let mut model: Option<String> = None;
+        let mut total_input = 0u64;
+        let mut total_output = 0u64;
+        let mut total_reasoning = 0u64;
+        let mut total_cache_read = 0u64;
+        let mut total_cache_write = 0u64;
+        let mut tool_calls: Vec<String> = Vec::new();
+        let mut file_size = file_info.size;
+
+        if let Some(ref sid) = session_id {
+            for message_path in self.message_files(sid) {
+                let Ok(content) = fs::read_to_string(&message_path) else {
+                    continue;
+                };
+                file_size += content.len() as u64;
+                let Ok(message) = serde_json::from_str::<Value>(&content) else {
+                    continue;
+                };
+
+                observe_times(message.get("time"));
+
+                if model.is_none()
+                    && let Some(m) = message.get("modelID").and_then(|v| v.as_str())
+                {
+                    model = Some(m.to_string());
+                }
+
+                if let Some(tokens) = message.get("tokens") {
+                    let get = |v: Option<&Value>| v.and_then(|v| v.as_u64()).unwrap_or(0);
+                    total_input = total_input.saturating_add(get(tokens.get("input")));
+                    total_output = total_output.saturating_add(get(tokens.get("output")));
+                    total_reasoning = total_reasoning.saturating_add(get(tokens.get("reasoning")));
+                    if let Some(cache) = tokens.get("cache") {
+                        total_cache_read = total_cache_read.saturating_add(get(cache.get("read")));
+                        total_cache_write = total_cache_write.saturating_add(get(cache.get("write")));
+                    }
+                }
+
+                if let Some(message_id) = message.get("id").and_then(|v| v.as_str()) {
+                    for part in self.part_files(sid, message_id) {
+                        let Some(part) = fs::read_to_string(&part)
+                            .ok()
+                            .and_then(|c| serde_json::from_str::<Value>(&c).ok())
+                        else {
+                            continue;
+                        };
+                        if part.get("type").and_then(|t| t.as_str()) == Some("tool")
+                            && let Some(tool) = part.get("tool").and_then(|t| t.as_str())
+                        {
+                            tool_calls.push(tool.to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        let tokens = if total_input > 0 || total_output > 0 {
+            Some(TokenInfo {
+                input: total_input,
+                output: total_output,
+                cached: total_cache_read,
+                cache_creation: total_cache_write,
+                reasoning: total_reasoning,
+                total: total_input + total_output + total_reasoning + total_cache_read + total_cache_write,
+            })
+        } else {
+            None
+        };
+
+        let (started_at, ended_at) = activity.or_file_times(file_info.created_at, file_info.modified_at);
+
+        Ok(AgentRecord {
+            agent_type: AgentType::OpenCode,
+            file_path: file_info.path.to_string_lossy().to_string(),
+            created_at: file_info.created_at,
+            modified_at: file_info.modified_at,
+            started_at,
+            ended_at,
+            file_size,
+            session_id,
+            parent_session_id,
+            model,
+            cwd,
+            title: session.get("title").and_then(|v| v.as_str()).and_then(session_title),
+            tokens,
+            tool_calls,
+            days: Vec::new(),
+            events: Vec::new(),
+            format: None,
+            cli_version: None,
+            git_branch: None,
+            git_commit: None,
+            repo_root: None,
+            repo_remote: None,
+            source_missing: false,
+        })
+    }
+
+    fn message_files(&self, session_id: &str) -> Vec<PathBuf> {
+        let dirs = [
+            self.root.join("message").join(session_id),
+            self.root.join("session").join("message").join(session_id),
+        ];
+        json_files_in(&dirs)
+    }
+
+    fn part_files(&self, session_id: &str, message_id: &str) -> Vec<PathBuf> {
+        let dirs = [
+            self.root.join("part").join(message_id),
+            self.root.join("session").join("part").join(session_id).join(message_id),
+        ];
+        json_files_in(&dirs)
+    }
+}
+
+impl AgentScanner for OpenCodeScanner {
+    fn agent_type(&self) -> AgentType {
+        AgentType::OpenCode
+    }
+
+    fn roots(&self) -> Vec<PathBuf> {
+        vec![self.root.join("session")]
+    }
+
+    fn matches(&self, path: &Path) -> bool {
+        // Session files sit exactly two levels below `session/`; the legacy
+        // `session/message` and `session/part` trees hold messages instead.
+        let Ok(relative) = path.strip_prefix(self.root.join("session")) else {
+            return false;
+        };
+        let components: Vec<_> = relative.components().collect();
+        path.extension().is_some_and(|ext| ext == "json")
+            && components.len() == 2
+            && !matches!(components[0].as_os_str().to_str(), Some("message") | Some("part"))
+    }
+
+    fn files(&self, failures: &mut Vec<ScanFailure>) -> Vec<FileInfo> {
+        let mut files = FileScanner::new(self.root.join("session")).walk_matching(|p| self.matches(p), failures);
+        // A session's file is rarely rewritten as it goes on; its messages
+        // and parts are, so the newest of them dates the session.
+        for file in &mut files {
+            let Some(session_id) = file.path.file_stem().map(|s| s.to_string_lossy().to_string()) else {
+                continue;
+            };
+            for message in self.message_files(&session_id) {
+                file.touched_with(&message);
+                let Some(message_id) = message.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                for part in self.part_files(&session_id, message_id) {
+                    file.touched_with(&part);
+                }
+            }
+        }
+        files
+    }
+
+    fn parse(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        Ok(vec![self.parse_session_file(file_info)?])
+    }
+}
+
+/// JSON files directly inside any of `dirs`, sorted by name (OpenCode ids
+/// sort chronologically).
+fn json_files_in(dirs: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = dirs
+        .iter()
+        .filter_map(|dir| fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use std::time::{Duration, SystemTime};
+
+    fn write(path: &Path, json: Value) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, json.to_string()).unwrap();
+    }
+
+    #[test]
+    fn sessions_are_read_from_their_messages_and_parts() {
+        let home = std::env::temp_dir().join(format!("agent-insights-opencode-{}", std::process::id()));
+        let scanner = OpenCodeScanner::new(&home.to_string_lossy());
+        let session = scanner.root.join("session").join("proj").join("ses_1.json");
+        write(&session, serde_json::json!({
+            "id": "ses_1",
+            "parentID": "ses_0",
+            "directory": "/work/app",
+            "title": "Fix the build",
+            "time": { "created": 1_735_700_000_000i64, "updated": 1_735_700_060_000i64 }
+        }));
+        write(&scanner.root.join("message").join("ses_1").join("msg_1.json"), serde_json::json!({
+            "id": "msg_1",
+            "modelID": "claude-sonnet-4",
+            "time": { "created": 1_735_700_010_000i64, "completed": 1_735_700_120_000i64 },
+            "tokens": { "input": 100, "output": 20, "reasoning": 5, "cache": { "read": 300, "write": 40 } }
+        }));
+        write(&scanner.root.join("message").join("ses_1").join("msg_2.json"), serde_json::json!({
+            "id": "msg_2",
+            "tokens": { "input": 10, "output": 2 }
+        }));
+        write(&scanner.root.join("part").join("msg_1").join("prt_1.json"), serde_json::json!({
+            "type": "tool", "tool": "bash"
+        }));
+        write(&scanner.root.join("part").join("msg_1").join("prt_2.json"), serde_json::json!({
+            "type": "text", "text": "done"
+        }));
+        // The session file itself was last written long before its parts
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        fs::File::options().write(true).open(&session).unwrap().set_modified(old).unwrap();
+
+        let files = scanner.files(&mut Vec::new());
+        let records = scanner.parse(&files[0]).unwrap();
+        fs::remove_dir_all(&home).unwrap();
+
+        assert_eq!(files.len(), 1);
+        assert!(files[0].modified_at > DateTime::<Utc>::from(old));
+        let record = &records[0];
+        assert_eq!(record.session_id.as_deref(), Some("ses_1"));
+        assert_eq!(record.parent_session_id.as_deref(), Some("ses_0"));
+        assert_eq!(record.cwd.as_deref(), Some("/work/app"));
+        assert_eq!(record.model.as_deref(), Some("claude-sonnet-4"));
+        assert_eq!(record.tool_calls, vec!["bash"]);
+        let tokens = record.tokens.as_ref().unwrap();
+        assert_eq!((tokens.input, tokens.output, tokens.cached, tokens.cache_creation), (110, 22, 300, 40));
+        assert_eq!(tokens.total, 110 + 22 + 5 + 300 + 40);
+        assert_eq!(record.started_at, from_unix(1_735_700_000_000).unwrap());
+        assert_eq!(record.ended_at, from_unix(1_735_700_120_000).unwrap());
+
+        // The heatmap counts the same total as the record, reasoning included.
+        let heatmaps = crate::services::AggregationService::aggregate_by_agent(records.clone());
+        let heatmap = &heatmaps["OpenCode"];
+        assert_eq!(heatmap.token_stats.total_tokens, tokens.total);
+        assert_eq!(heatmap.token_stats.reasoning_tokens, Some(5));
+        assert_eq!(heatmap.data.iter().map(|day| day.tokens).sum::<u64>(), tokens.total);
+    }
+
+    #[test]
+    fn only_session_files_match() {
+        let scanner = OpenCodeScanner::new("/home/me");
+        let session = scanner.root.join("session");
+        assert!(scanner.matches(&session.join("proj").join("ses_1.json")));
+        assert!(scanner.matches(&session.join("info").join("ses_1.json")));
+        assert!(!scanner.matches(&session.join("message").join("ses_1.json")));
+        assert!(!scanner.matches(&session.join("part").join("ses_1").join("msg_1.json")));
+        assert!(!scanner.matches(&session.join("proj").join("ses_1.txt")));
+    }
+}
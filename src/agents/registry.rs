@@ -0,0 +1,379 @@
+#[cfg(feature = "cache")]
+use crate::cache::CacheDb;
+use crate::domain::{AgentRecord, AgentType, ScanFailure, ScanReport};
+use crate::scanner::{default_workers, fill_from_checkout, parallel_map, FileInfo, FileScanner};
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[cfg(feature = "cache")]
+use super::{CachedScanner, CrushScanner};
+use super::{
+    AiderScanner, ClaudeScanner, CodexScanner, CodexiaScanner, CustomScanner, GeminiScanner,
+    OpenCodeScanner,
+};
+
+/// A source of agent session files.
+///
+/// Implementors describe where an agent keeps its sessions and how to turn
+/// one session file into an [`AgentRecord`]; walking the directories is
+/// shared. Register an implementation with [`ScannerRegistry::register`] to
+/// have it collected, served by the API and watched for changes.
+pub trait AgentScanner: Send + Sync {
+    /// The agent whose records this scanner produces.
+    fn agent_type(&self) -> AgentType;
+
+    /// Directories searched (recursively) for session files.
+    fn roots(&self) -> Vec<PathBuf>;
+
+    /// Whether `path` is a session file this scanner can parse.
+    fn matches(&self, path: &Path) -> bool;
+
+    /// Parse a session file. Most agents write one session per file, but
+    /// some (e.g. Aider) append every session to the same transcript.
+    fn parse(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>>;
+
+    /// [`parse`] a file and fill in the repository details its log left out
+    /// from the checkout the session ran in. Scans read files through this,
+    /// so wrappers that keep what was parsed override it rather than `parse`.
+    ///
+    /// [`parse`]: AgentScanner::parse
+    fn read(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+        let mut records = self.parse(file_info)?;
+        records.iter_mut().for_each(fill_from_checkout);
+        Ok(records)
+    }
+
+    /// Every matching file under the scanner's roots. Entries that cannot
+    /// be read are recorded in `failures`.
+    fn files(&self, failures: &mut Vec<ScanFailure>) -> Vec<FileInfo> {
+        let mut files = Vec::new();
+        for root in self.roots() {
+            files.extend(FileScanner::new(root).walk_matching(|p| self.matches(p), failures));
+        }
+        files
+    }
+
+    /// Version of this scanner's parser. Bump it when a parser change
+    /// alters the records produced from the same file, so that records
+    /// cached by the old parser are parsed again.
+    fn parser_version(&self) -> u32 {
+        1
+    }
+
+    /// Records for an unchanged file, for scanners that keep a cache.
+    fn cached(&self, _file_info: &FileInfo) -> Option<Vec<AgentRecord>> {
+        None
+    }
+
+    /// Whether [`cached`] can answer at all, so that parsed files count as
+    /// cache misses.
+    ///
+    /// [`cached`]: AgentScanner::cached
+    fn uses_cache(&self) -> bool {
+        false
+    }
+
+    /// Parse every matching file, skipping those that fail to parse.
+    fn scan(&self) -> Result<Vec<AgentRecord>> {
+        Ok(self.scan_with_progress(&|_| {})?.records)
+    }
+
+    /// Like [`scan`], calling `progress` after each file and reporting what
+    /// happened to every file. Files are parsed on a bounded number of
+    /// threads; this blocks until all are done.
+    ///
+    /// [`scan`]: AgentScanner::scan
+    fn scan_with_progress(&self, progress: &(dyn Fn(ScanProgress) + Sync)) -> Result<Scan> {
+        let agent = self.agent_type();
+        let mut report = ScanReport::new(agent.to_string());
+        let mut failures = Vec::new();
+        let files = self.files(&mut failures);
+        for failure in failures {
+            report.fail(failure.path, failure.reason);
+        }
+
+        let total = files.len();
+        let done = AtomicUsize::new(0);
+        let outcomes = parallel_map(&files, default_workers(), |file_info| {
+            let outcome = match self.cached(file_info) {
+                Some(records) => (true, Ok(records)),
+                None => (false, self.read(file_info)),
+            };
+            progress(ScanProgress {
+                agent: agent.clone(),
+                done: done.fetch_add(1, Ordering::Relaxed) + 1,
+                total,
+            });
+            outcome
+        });
+
+        report.files_seen = total;
+        let mut records = Vec::new();
+        for (file_info, (cached, parsed)) in files.into_iter().zip(outcomes) {
+            match parsed {
+                Ok(parsed) => {
+                    if cached {
+                        report.cached += 1;
+                    } else {
+                        report.parsed += 1;
+                        if self.uses_cache() {
+                            report.cache_misses += 1;
+                        }
+                    }
+                    if parsed.is_empty() {
+                        report.skipped += 1;
+                    }
+                    // Records from one file share its format, and so its count
+                    report.invalid_lines += parsed.iter()
+                        .filter_map(|r| r.format.as_ref())
+                        .map(|f| f.invalid_lines)
+                        .max()
+                        .unwrap_or(0);
+                    let drift = parsed.iter().find_map(|r| r.format.as_ref()?.drift.clone());
+                    if let Some(reason) = drift {
+                        report.format_drift.push(ScanFailure { path: file_info.path, reason });
+                    }
+                    records.extend(parsed);
+                }
+                Err(e) => report.fail(file_info.path, format!("{:#}", e)),
+            }
+        }
+        report.records = records.len();
+
+        Ok(Scan { records, report })
+    }
+
+    /// Directories the file watcher should observe. Defaults to [`roots`],
+    /// but scanners with very broad roots may narrow or disable watching.
+    ///
+    /// [`roots`]: AgentScanner::roots
+    fn watch_roots(&self) -> Vec<PathBuf> {
+        self.roots()
+    }
+
+    /// Whether a path reported by the file watcher belongs to this scanner.
+    fn owns(&self, path: &Path) -> bool {
+        self.roots().iter().any(|root| path.starts_with(root)) && self.matches(path)
+    }
+}
+
+/// Records produced by a scan, with what happened to each file.
+#[derive(Debug, Clone)]
+pub struct Scan {
+    pub records: Vec<AgentRecord>,
+    pub report: ScanReport,
+}
+
+/// How far a scanner has got through its files.
+#[derive(Debug, Clone)]
+pub struct ScanProgress {
+    pub agent: AgentType,
+    pub done: usize,
+    pub total: usize,
+}
+
+/// The set of scanners consulted by collection, the API and the file watcher.
+#[derive(Default, Clone)]
+pub struct ScannerRegistry {
+    scanners: Vec<Arc<dyn AgentScanner>>,
+    /// When set, every registered scanner is wrapped in a [`CachedScanner`].
+    #[cfg(feature = "cache")]
+    cache: Option<Arc<CacheDb>>,
+}
+
+impl ScannerRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with the built-in scanners.
+    pub fn with_defaults(home_dir: &str) -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(ClaudeScanner::new(home_dir)));
+        registry.register(Arc::new(GeminiScanner::new(home_dir)));
+        registry.register(Arc::new(CodexScanner::new(home_dir)));
+        registry.register(Arc::new(CodexiaScanner::new(home_dir)));
+        registry.register_builtins(home_dir);
+        registry
+    }
+
+    /// Registry with the built-in scanners, each answering unchanged files
+    /// from `cache`. Scanners registered later are cached too.
+    #[cfg(feature = "cache")]
+    pub fn with_cache(home_dir: &str, cache: Arc<CacheDb>) -> Self {
+        let mut registry = Self { cache: Some(Arc::clone(&cache)), ..Self::new() };
+        registry.register(Arc::new(ClaudeScanner::with_cache(home_dir, Arc::clone(&cache))));
+        registry.register(Arc::new(GeminiScanner::new(home_dir)));
+        registry.register(Arc::new(CodexScanner::with_cache(home_dir, Arc::clone(&cache))));
+        registry.register(Arc::new(CodexiaScanner::with_cache(home_dir, cache)));
+        registry.register_builtins(home_dir);
+        registry
+    }
+
+    /// The built-in scanners that do not checkpoint their parses in the
+    /// cache, followed by any agents described in the custom agent config.
+    fn register_builtins(&mut self, home_dir: &str) {
+        self.register(Arc::new(AiderScanner::from_env()));
+        self.register(Arc::new(OpenCodeScanner::new(home_dir)));
+        #[cfg(feature = "cache")]
+        self.register(Arc::new(CrushScanner::new(home_dir)));
+
+        for scanner in CustomScanner::load_all(home_dir) {
+            self.register(Arc::new(scanner));
+        }
+    }
+
+    /// Add a scanner. A scanner whose agent id is already registered
+    /// replaces the previous one in place, so that [`get`] and the API
+    /// routes built on it find exactly one scanner per id; a custom agent
+    /// named after a built-in one takes its place.
+    ///
+    /// [`get`]: ScannerRegistry::get
+    pub fn register(&mut self, scanner: Arc<dyn AgentScanner>) {
+        #[cfg(feature = "cache")]
+        let scanner: Arc<dyn AgentScanner> = match &self.cache {
+            Some(cache) => Arc::new(CachedScanner::new(scanner, Arc::clone(cache))),
+            None => scanner,
+        };
+        let agent = scanner.agent_type();
+        match self.scanners.iter().position(|s| s.agent_type().id() == agent.id()) {
+            Some(index) => {
+                let replaced = self.scanners[index].agent_type();
+                if replaced != agent {
+                    tracing::warn!("Scanner for {:?} replaces {:?}, which has the same id", agent, replaced);
+                }
+                self.scanners[index] = scanner;
+            }
+            None => self.scanners.push(scanner),
+        }
+    }
+
+    /// Look up a scanner by its agent id, e.g. `"claude"`.
+    pub fn get(&self, id: &str) -> Option<&Arc<dyn AgentScanner>> {
+        let id = id.to_lowercase();
+        self.scanners.iter().find(|s| s.agent_type().id() == id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Arc<dyn AgentScanner>> {
+        self.scanners.iter()
+    }
+
+    /// The agent owning a session file, if any registered scanner claims it.
+    pub fn agent_for_path(&self, path: &Path) -> Option<AgentType> {
+        self.scanners
+            .iter()
+            .find(|s| s.owns(path))
+            .map(|s| s.agent_type())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agents::custom::CustomAgentDefinition;
+
+    fn custom(name: &str) -> Arc<dyn AgentScanner> {
+        let definition: CustomAgentDefinition = serde_json::from_value(serde_json::json!({
+            "name": name, "root": "/nonexistent", "format": "jsonl"
+        }))
+        .unwrap();
+        Arc::new(CustomScanner::new(definition, "/nonexistent").unwrap())
+    }
+
+    fn ids(registry: &ScannerRegistry) -> Vec<String> {
+        registry.iter().map(|s| s.agent_type().id()).collect()
+    }
+
+    #[test]
+    fn scanners_keep_their_registration_order() {
+        let mut registry = ScannerRegistry::new();
+        registry.register(Arc::new(GeminiScanner::new("/nonexistent")));
+        registry.register(Arc::new(CodexScanner::new("/nonexistent")));
+        registry.register(custom("Forge"));
+
+        assert_eq!(ids(&registry), vec!["gemini", "codex", "forge"]);
+    }
+
+    #[test]
+    fn a_scanner_with_a_registered_id_replaces_it_in_place() {
+        let mut registry = ScannerRegistry::new();
+        registry.register(Arc::new(ClaudeScanner::new("/nonexistent")));
+        registry.register(Arc::new(CodexScanner::new("/nonexistent")));
+        registry.register(custom("Claude"));
+        registry.register(Arc::new(CodexScanner::new("/elsewhere")));
+
+        assert_eq!(ids(&registry), vec!["claude", "codex"]);
+        assert_eq!(registry.get("claude").unwrap().agent_type(), AgentType::Custom("Claude".to_string()));
+        assert_eq!(registry.get("codex").unwrap().roots(), vec![PathBuf::from("/elsewhere/.codex")]);
+    }
+
+    #[test]
+    fn scanners_are_looked_up_by_id_ignoring_case() {
+        let mut registry = ScannerRegistry::new();
+        registry.register(Arc::new(CodexScanner::new("/nonexistent")));
+        registry.register(custom("Forge"));
+
+        assert_eq!(registry.get("CODEX").unwrap().agent_type(), AgentType::Codex);
+        assert_eq!(registry.get("forge").unwrap().agent_type(), AgentType::Custom("Forge".to_string()));
+        assert!(registry.get("claude").is_none());
+    }
+
+    /// Parses rollouts, except `empty.jsonl`, which holds no sessions, and
+    /// `broken.jsonl`, which fails.
+    struct Fixture {
+        root: PathBuf,
+    }
+
+    impl AgentScanner for Fixture {
+        fn agent_type(&self) -> AgentType {
+            AgentType::Custom("Fixture".to_string())
+        }
+
+        fn roots(&self) -> Vec<PathBuf> {
+            vec![self.root.clone()]
+        }
+
+        fn matches(&self, path: &Path) -> bool {
+            path.extension().is_some_and(|ext| ext == "jsonl")
+        }
+
+        fn parse(&self, file_info: &FileInfo) -> Result<Vec<AgentRecord>> {
+            match file_info.path.file_stem().and_then(|s| s.to_str()) {
+                Some("empty") => Ok(Vec::new()),
+                Some("broken") => anyhow::bail!("not a session"),
+                _ => Ok(vec![crate::agents::codex::parse_rollout(file_info, self.agent_type())?]),
+            }
+        }
+    }
+
+    #[test]
+    fn scan_report_accounts_for_every_file() {
+        let dir = std::env::temp_dir().join(format!("agent-insights-report-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let meta = r#"{"timestamp":"2025-04-20T10:00:00Z","type":"session_meta","payload":{"id":"s1"}}"#;
+        std::fs::write(dir.join("a.jsonl"), format!("{}\nnot json\n{{\"half\":\n", meta)).unwrap();
+        std::fs::write(dir.join("b.jsonl"), format!("{}\n[oops\n", meta)).unwrap();
+        std::fs::write(dir.join("empty.jsonl"), "").unwrap();
+        std::fs::write(dir.join("broken.jsonl"), "").unwrap();
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let scan = Fixture { root: dir.clone() }.scan_with_progress(&|_| {}).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let report = &scan.report;
+        assert_eq!(
+            (report.files_seen, report.parsed, report.cached, report.cache_misses, report.skipped, report.failed),
+            (4, 3, 0, 0, 1, 1)
+        );
+        assert_eq!((report.records, scan.records.len()), (2, 2));
+        assert_eq!(report.invalid_lines, 3);
+        assert!(report.failures[0].path.ends_with("broken.jsonl"));
+        assert_eq!(report.failures[0].reason, "not a session");
+        assert_eq!(
+            report.to_string(),
+            "Fixture: 4 files, 3 parsed, 0 cached, 1 skipped, 1 failed, 2 records, 3 invalid lines skipped"
+        );
+    }
+}
@@ -1,13 +1,22 @@
 use crate::domain::HeatmapData;
-use crate::services::{AggregationService, CollectionService};
+use crate::services::{AggregateOptions, AggregationService, CollectionService};
 use axum::{
-    extract::Path,
+    extract::{Path, Query},
     http::StatusCode,
     Json,
 };
+use serde::Deserialize;
 use std::collections::HashMap;
 
-pub async fn get_all_heatmaps() -> Result<Json<HashMap<String, HeatmapData>>, StatusCode> {
+#[derive(Debug, Default, Deserialize)]
+pub struct HeatmapsQuery {
+    /// Show sub-agent sessions as separate heatmaps instead of rolling them
+    /// into their parent agent.
+    #[serde(default)]
+    pub split_subagents: bool,
+}
+
+pub async fn get_all_heatmaps(Query(query): Query<HeatmapsQuery>) -> Result<Json<HashMap<String, HeatmapData>>, StatusCode> {
     let service = CollectionService::new()
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -17,7 +26,10 @@ pub async fn get_all_heatmaps() -> Result<Json<HashMap<String, HeatmapData>>, St
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
 
-    let heatmaps = AggregationService::aggregate_by_agent(records);
+    let options = AggregateOptions {
+        split_subagents: query.split_subagents,
+    };
+    let heatmaps = AggregationService::aggregate_by_agent_with(records, options);
     Ok(Json(heatmaps))
 }
 
@@ -25,15 +37,12 @@ pub async fn get_agent_heatmap(Path(agent): Path<String>) -> Result<Json<Heatmap
     let service = CollectionService::new()
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let records = match agent.to_lowercase().as_str() {
-        "claude" => service.collect_claude().await,
-        "gemini" => service.collect_gemini().await,
-        "codex" => service.collect_codex().await,
-        _ => return Err(StatusCode::NOT_FOUND),
-    }.map_err(|e| {
-        tracing::error!("Failed to collect {} records: {}", agent, e);
-        StatusCode::INTERNAL_SERVER_ERROR
-    })?;
+    let records = service.collect_agent(&agent).await
+        .ok_or(StatusCode::NOT_FOUND)?
+        .map_err(|e| {
+            tracing::error!("Failed to collect {} records: {}", agent, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
 
     let heatmap = AggregationService::aggregate_by_date(records);
     Ok(Json(heatmap))
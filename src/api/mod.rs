@@ -1,3 +1,7 @@
 pub mod heatmap;
+pub mod scan;
+pub mod sessions;
 
 pub use heatmap::*;
+pub use scan::*;
+pub use sessions::*;
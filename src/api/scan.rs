@@ -0,0 +1,19 @@
+use crate::domain::ScanReport;
+use crate::services::CollectionService;
+use axum::{http::StatusCode, Json};
+
+/// Rescan every agent and report, per agent, how many files were parsed,
+/// answered from the cache, skipped or failed, with the reason for each
+/// failure.
+pub async fn get_scan_report() -> Result<Json<Vec<ScanReport>>, StatusCode> {
+    let service = CollectionService::new()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let reports = service
+        .scan_all()
+        .await
+        .into_iter()
+        .map(|scan| scan.report)
+        .collect();
+    Ok(Json(reports))
+}
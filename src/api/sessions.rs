@@ -0,0 +1,42 @@
+use crate::domain::SessionSummary;
+use crate::services::CollectionService;
+use axum::{extract::Query, http::StatusCode, Json};
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SessionsQuery {
+    /// Only sessions with activity on this day, `YYYY-MM-DD`.
+    pub date: Option<String>,
+    /// Only sessions of this agent id, e.g. `claude`.
+    pub agent: Option<String>,
+}
+
+/// Sessions with their titles, most recent first.
+pub async fn get_sessions(Query(query): Query<SessionsQuery>) -> Result<Json<Vec<SessionSummary>>, StatusCode> {
+    let date = query.date
+        .as_deref()
+        .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d"))
+        .transpose()
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let service = CollectionService::new()
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let records = match query.agent.as_deref() {
+        Some(agent) => service.collect_agent(agent).await.ok_or(StatusCode::NOT_FOUND)?,
+        None => service.collect_all().await,
+    }
+    .map_err(|e| {
+        tracing::error!("Failed to collect records: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let mut sessions: Vec<SessionSummary> = records
+        .iter()
+        .filter(|r| date.is_none_or(|d| r.active_on(d)))
+        .map(SessionSummary::from)
+        .collect();
+    sessions.sort_by_key(|s| std::cmp::Reverse(s.started_at));
+    Ok(Json(sessions))
+}
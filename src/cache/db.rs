@@ -1,12 +1,22 @@
-use crate::domain::{AgentRecord, AgentType};
+use crate::domain::{AgentEvent, AgentRecord, AgentType, DayUsage, EventKind, LogFormat, TokenInfo};
+use super::migrations;
 use anyhow::Result;
-use chrono::{DateTime, Utc};
-use rusqlite::{params, Connection};
-use std::path::PathBuf;
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::io::Read;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
 pub struct CacheDb {
     conn: Mutex<Connection>,
+    /// Whether [`store_content`] keeps raw session files.
+    ///
+    /// [`store_content`]: CacheDb::store_content
+    keep_content: bool,
 }
 
 impl CacheDb {
@@ -25,110 +35,72 @@ impl CacheDb {
         }
 
         let conn = Connection::open(&path)?;
-        let db = Self { conn: Mutex::new(conn) };
+        let db = Self { conn: Mutex::new(conn), keep_content: false };
         db.init()?;
         tracing::info!("Cache database initialized at {:?}", path);
         Ok(db)
     }
 
     fn init(&self) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS file_cache (
-                file_path TEXT PRIMARY KEY,
-                agent_type TEXT NOT NULL,
-                created_at TEXT NOT NULL,
-                modified_at TEXT NOT NULL,
-                file_size INTEGER NOT NULL,
-                session_id TEXT,
-                tokens_input INTEGER,
-                tokens_output INTEGER,
-                tokens_cached INTEGER,
-                tokens_reasoning INTEGER,
-                tokens_total INTEGER,
-                tool_calls TEXT,
-                cached_at TEXT NOT NULL,
-                cwd TEXT
-            )",
-            [],
-        )?;
+        let mut conn = self.conn.lock().unwrap();
+        migrations::migrate(&mut conn)
+    }
 
-        Ok(())
+    /// Also keep a compressed copy of every parsed session file, so that
+    /// sessions whose file was deleted can be parsed again later.
+    pub fn with_content(mut self, keep: bool) -> Self {
+        self.keep_content = keep;
+        self
+    }
+
+    pub fn keeps_content(&self) -> bool {
+        self.keep_content
+    }
+
+    /// Schema version the database is at.
+    pub fn schema_version(&self) -> Result<i64> {
+        migrations::schema_version(&self.conn.lock().unwrap())
     }
 
-    pub fn get_cached_record(&self, file_path: &str, modified_at: &DateTime<Utc>) -> Result<Option<AgentRecord>> {
+    /// The record cached for `file_path` at `modified_at`, unless it was
+    /// produced by a different version of the agent's parser.
+    pub fn get_cached_record(
+        &self,
+        file_path: &str,
+        modified_at: &DateTime<Utc>,
+        parser_version: u32,
+    ) -> Result<Option<AgentRecord>> {
         let conn = self.conn.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT agent_type, created_at, modified_at, file_size, session_id,
-                    tokens_input, tokens_output, tokens_cached, tokens_reasoning, tokens_total, tool_calls, model, cwd
-             FROM file_cache
-             WHERE file_path = ?1 AND modified_at = ?2"
-        )?;
+        let sql = format!(
+            "SELECT {} FROM file_cache WHERE file_path = ?1 AND modified_at = ?2 AND parser_version = ?3",
+            RECORD_COLUMNS
+        );
+        let mut stmt = conn.prepare(&sql)?;
 
         let modified_str = modified_at.to_rfc3339();
-        let result = stmt.query_row(params![file_path, modified_str], |row| {
-            let agent_type_str: String = row.get(0)?;
-            let agent_type = match agent_type_str.as_str() {
-                "Claude" => AgentType::Claude,
-                "Gemini" => AgentType::Gemini,
-                "Codex" => AgentType::Codex,
-                _ => AgentType::Codexia,
-            };
-
-            let created_str: String = row.get(1)?;
-            let modified_str: String = row.get(2)?;
-
-            let tokens = if let (Some(input), Some(output), Some(cached), Some(reasoning), Some(total)) = (
-                row.get::<_, Option<i64>>(5)?,
-                row.get::<_, Option<i64>>(6)?,
-                row.get::<_, Option<i64>>(7)?,
-                row.get::<_, Option<i64>>(8)?,
-                row.get::<_, Option<i64>>(9)?,
-            ) {
-                Some(crate::domain::TokenInfo {
-                    input: input as u64,
-                    output: output as u64,
-                    cached: cached as u64,
-                    cache_creation: 0,
-                    reasoning: reasoning as u64,
-                    total: total as u64,
-                })
-            } else {
-                None
-            };
-
-            let tool_calls: Vec<String> = row.get::<_, Option<String>>(10)?
-                .and_then(|json_str| serde_json::from_str(&json_str).ok())
-                .unwrap_or_default();
-
-            Ok(AgentRecord {
-                agent_type,
-                file_path: file_path.to_string(),
-                created_at: created_str.parse().unwrap_or_else(|_| Utc::now()),
-                modified_at: modified_str.parse().unwrap_or_else(|_| Utc::now()),
-                file_size: row.get::<_, i64>(3)? as u64,
-                session_id: row.get(4)?,
-                model: row.get(11)?,
-                cwd: row.get(12)?,
-                tokens,
-                tool_calls,
-            })
-        });
-
-        match result {
-            Ok(record) => Ok(Some(record)),
+        match stmt.query_row(params![file_path, modified_str, parser_version], record_from_row) {
+            Ok(mut record) => {
+                record.events = load_events(&conn, "WHERE file_path = ?1", params![file_path])?
+                    .remove(file_path)
+                    .unwrap_or_default();
+                Ok(Some(record))
+            }
             Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
             Err(e) => Err(e.into()),
         }
     }
 
-    pub fn cache_record(&self, record: &AgentRecord) -> Result<()> {
-        let agent_type_str = format!("{:?}", record.agent_type);
+    /// Store `record`, stamped with the version of the parser that produced it.
+    pub fn cache_record(&self, record: &AgentRecord, parser_version: u32) -> Result<()> {
+        let agent_type_str = agent_type_to_sql(&record.agent_type);
         let created_str = record.created_at.to_rfc3339();
         let modified_str = record.modified_at.to_rfc3339();
+        let started_str = record.started_at.to_rfc3339();
+        let ended_str = record.ended_at.to_rfc3339();
         let cached_at = Utc::now().to_rfc3339();
 
-        let (tokens_input, tokens_output, tokens_cached, tokens_reasoning, tokens_total) = if let Some(ref tokens) = record.tokens {
+        let tokens = record.tokens.as_ref();
+        let (tokens_input, tokens_output, tokens_cached, tokens_reasoning, tokens_total) = if let Some(tokens) = tokens {
             (
                 Some(tokens.input as i64),
                 Some(tokens.output as i64),
@@ -139,15 +111,23 @@ impl CacheDb {
         } else {
             (None, None, None, None, None)
         };
+        let tokens_cache_creation = tokens.map(|t| t.cache_creation as i64);
 
         let tool_calls_json = serde_json::to_string(&record.tool_calls)?;
+        let days_json = serde_json::to_string(&record.days)?;
+        let format_json = record.format.as_ref().map(serde_json::to_string).transpose()?;
 
-        let conn = self.conn.lock().unwrap();
-        conn.execute(
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM file_records WHERE file_path = ?1", params![record.file_path])?;
+        tx.execute(
             "INSERT OR REPLACE INTO file_cache
              (file_path, agent_type, created_at, modified_at, file_size, session_id,
-              tokens_input, tokens_output, tokens_cached, tokens_reasoning, tokens_total, tool_calls, cached_at, model, cwd)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+              tokens_input, tokens_output, tokens_cached, tokens_reasoning, tokens_total, tool_calls, cached_at, model, cwd,
+              parent_session_id, started_at, ended_at, days, format, git_branch, git_commit, repo_root, repo_remote,
+              cli_version, title, parser_version, tokens_cache_creation)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20,
+                     ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28)",
             params![
                 record.file_path,
                 agent_type_str,
@@ -164,70 +144,351 @@ impl CacheDb {
                 cached_at,
                 record.model,
                 record.cwd,
+                record.parent_session_id,
+                started_str,
+                ended_str,
+                days_json,
+                format_json,
+                record.git_branch,
+                record.git_commit,
+                record.repo_root,
+                record.repo_remote,
+                record.cli_version,
+                record.title,
+                parser_version,
+                tokens_cache_creation,
             ],
         )?;
 
+        tx.execute("DELETE FROM session_events WHERE file_path = ?1", params![record.file_path])?;
+        {
+            let mut insert = tx.prepare(
+                "INSERT INTO session_events
+                 (file_path, seq, kind, timestamp, model, message_id, tool_name, tool_call_id, outcome,
+                  tokens_input, tokens_output, tokens_cached, tokens_cache_creation, tokens_reasoning, tokens_total)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)"
+            )?;
+            for (seq, event) in record.events.iter().enumerate() {
+                let usage = event.usage.as_ref();
+                insert.execute(params![
+                    record.file_path,
+                    seq as i64,
+                    event.kind.as_str(),
+                    event.timestamp.map(|t| t.to_rfc3339()),
+                    event.model,
+                    event.message_id,
+                    event.tool_name,
+                    event.tool_call_id,
+                    event.outcome.map(|o| o.as_str()),
+                    usage.map(|u| u.input as i64),
+                    usage.map(|u| u.output as i64),
+                    usage.map(|u| u.cached as i64),
+                    usage.map(|u| u.cache_creation as i64),
+                    usage.map(|u| u.reasoning as i64),
+                    usage.map(|u| u.total as i64),
+                ])?;
+            }
+        }
+        tx.commit()?;
+
         Ok(())
     }
 
-    /// Return all cached records, optionally filtered to created_at >= since (ISO date "YYYY-MM-DD").
-    pub fn get_all_records(&self, since: Option<&str>) -> Result<Vec<AgentRecord>> {
+    /// Store what parsing the file at `file_path` produced. A file holding a
+    /// single session of its own is cached as a record like any other;
+    /// anything else (a transcript or database of several sessions, or a
+    /// file with none) is kept whole in `file_records`.
+    pub fn cache_records(
+        &self,
+        file_path: &str,
+        agent: &AgentType,
+        modified_at: &DateTime<Utc>,
+        records: &[AgentRecord],
+        parser_version: u32,
+    ) -> Result<()> {
+        if let [record] = records
+            && record.file_path == file_path
+        {
+            return self.cache_record(record, parser_version);
+        }
+
+        let records_json = serde_json::to_string(records)?;
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        for table in ["session_events", "file_cache"] {
+            tx.execute(&format!("DELETE FROM {} WHERE file_path = ?1", table), params![file_path])?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO file_records
+             (file_path, agent_type, modified_at, parser_version, records, cached_at, missing_since)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, NULL)",
+            params![
+                file_path,
+                agent_type_to_sql(agent),
+                modified_at.to_rfc3339(),
+                parser_version,
+                records_json,
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The records cached by [`cache_records`] for a file holding several
+    /// sessions, unless the file changed or the parser did.
+    ///
+    /// [`cache_records`]: CacheDb::cache_records
+    pub fn get_cached_file_records(
+        &self,
+        file_path: &str,
+        modified_at: &DateTime<Utc>,
+        parser_version: u32,
+    ) -> Result<Option<Vec<AgentRecord>>> {
+        let conn = self.conn.lock().unwrap();
+        let records: Option<String> = conn
+            .query_row(
+                "SELECT records FROM file_records
+                 WHERE file_path = ?1 AND modified_at = ?2 AND parser_version = ?3",
+                params![file_path, modified_at.to_rfc3339(), parser_version],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(records.map(|r| serde_json::from_str(&r)).transpose()?)
+    }
+
+    /// Events cached for `file_path` with its last record, in logged order,
+    /// whether or not the file has changed since.
+    pub fn get_events(&self, file_path: &str) -> Result<Vec<AgentEvent>> {
+        let conn = self.conn.lock().unwrap();
+        Ok(load_events(&conn, "WHERE file_path = ?1", params![file_path])?
+            .remove(file_path)
+            .unwrap_or_default())
+    }
+
+    /// Where parsing of `file_path` stopped last time, if it was recorded by
+    /// the same version of the parser.
+    pub fn get_checkpoint(&self, file_path: &str, parser_version: u32) -> Result<Option<ParseCheckpoint>> {
+        let conn = self.conn.lock().unwrap();
+        let result = conn.query_row(
+            "SELECT byte_offset, prefix_hash, state FROM parse_checkpoints
+             WHERE file_path = ?1 AND parser_version = ?2",
+            params![file_path, parser_version],
+            |row| {
+                Ok(ParseCheckpoint {
+                    offset: row.get::<_, i64>(0)? as u64,
+                    prefix_hash: row.get::<_, i64>(1)? as u64,
+                    state: row.get(2)?,
+                })
+            },
+        );
+        match result {
+            Ok(checkpoint) => Ok(Some(checkpoint)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save_checkpoint(&self, file_path: &str, parser_version: u32, checkpoint: &ParseCheckpoint) -> Result<()> {
         let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO parse_checkpoints (file_path, byte_offset, prefix_hash, state, parser_version)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                file_path,
+                checkpoint.offset as i64,
+                checkpoint.prefix_hash as i64,
+                checkpoint.state,
+                parser_version,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Deal with cached records whose session file no longer exists, as
+    /// `policy` says. Returns the number of records newly archived or pruned.
+    pub fn reconcile_missing(&self, policy: MissingFiles) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tracked: Vec<String> = conn
+            .prepare(
+                "SELECT file_path FROM file_cache WHERE missing_since IS NULL
+                 UNION SELECT file_path FROM file_records WHERE missing_since IS NULL",
+            )?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        let missing: Vec<String> = tracked.into_iter().filter(|p| !Path::new(p).exists()).collect();
+
+        let tx = conn.transaction()?;
+        for path in &missing {
+            match policy {
+                MissingFiles::Archive => mark_missing(&tx, path)?,
+                MissingFiles::Prune => forget(&tx, path)?,
+            }
+        }
+        tx.commit()?;
+        Ok(missing.len())
+    }
+
+    /// Flag the record of `file_path` as kept only in the cache.
+    pub fn mark_missing(&self, file_path: &str) -> Result<()> {
+        mark_missing(&self.conn.lock().unwrap(), file_path)?;
+        Ok(())
+    }
+
+    /// Drop everything cached for `file_path`.
+    pub fn forget(&self, file_path: &str) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        forget(&tx, file_path)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Keep a compressed copy of the session file at `path`, cached as
+    /// `file_path`. Does nothing unless enabled with [`with_content`].
+    ///
+    /// [`with_content`]: CacheDb::with_content
+    pub fn store_content(&self, file_path: &str, modified_at: &DateTime<Utc>, path: &Path) -> Result<()> {
+        if !self.keep_content {
+            return Ok(());
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        std::io::copy(&mut std::fs::File::open(path)?, &mut encoder)?;
+        let content = encoder.finish()?;
+
+        let conn = self.conn.lock().unwrap();
+        conn.execute(
+            "INSERT OR REPLACE INTO session_content (file_path, modified_at, content) VALUES (?1, ?2, ?3)",
+            params![file_path, modified_at.to_rfc3339(), content],
+        )?;
+        Ok(())
+    }
+
+    /// The raw session file kept for `file_path`, decompressed.
+    pub fn get_content(&self, file_path: &str) -> Result<Option<Vec<u8>>> {
+        let conn = self.conn.lock().unwrap();
+        let content: Option<Vec<u8>> = conn
+            .query_row(
+                "SELECT content FROM session_content WHERE file_path = ?1",
+                params![file_path],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let Some(content) = content else {
+            return Ok(None);
+        };
+        let mut raw = Vec::new();
+        GzDecoder::new(content.as_slice()).read_to_end(&mut raw)?;
+        Ok(Some(raw))
+    }
+
+    /// Records kept from the cache after their session file disappeared,
+    /// for every agent or only `agent`.
+    pub fn get_archived_records(&self, agent: Option<&AgentType>) -> Result<Vec<AgentRecord>> {
+        let conn = self.conn.lock().unwrap();
+        let agent = agent.map(agent_type_to_sql);
+        let filter = "WHERE missing_since IS NOT NULL AND (?1 IS NULL OR agent_type = ?1)";
+
+        let sql = format!("SELECT {} FROM file_cache {} ORDER BY started_at", RECORD_COLUMNS, filter);
+        let mut records: Vec<AgentRecord> = conn
+            .prepare(&sql)?
+            .query_map(params![agent], record_from_row)?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut events = load_events(
+            &conn,
+            &format!("WHERE file_path IN (SELECT file_path FROM file_cache {})", filter),
+            params![agent],
+        )?;
+        for record in &mut records {
+            record.events = events.remove(&record.file_path).unwrap_or_default();
+        }
+        records.extend(load_file_records(
+            &conn,
+            "WHERE missing_since IS NOT NULL AND (?1 IS NULL OR agent_type = ?1)",
+            params![agent],
+        )?);
+        records.sort_by_key(|r| r.started_at);
+        Ok(records)
+    }
 
-        let sql_with    = "SELECT file_path, agent_type, created_at, modified_at, file_size, session_id,
-                                  tokens_input, tokens_output, tokens_cached, tokens_reasoning, tokens_total, tool_calls, model, cwd
-                           FROM file_cache WHERE created_at >= ?1 ORDER BY created_at";
-        let sql_all     = "SELECT file_path, agent_type, created_at, modified_at, file_size, session_id,
-                                  tokens_input, tokens_output, tokens_cached, tokens_reasoning, tokens_total, tool_calls, model, cwd
-                           FROM file_cache ORDER BY created_at";
-
-        let mut stmt = conn.prepare(if since.is_some() { sql_with } else { sql_all })?;
-
-        fn parse_row(row: &rusqlite::Row) -> rusqlite::Result<AgentRecord> {
-            let agent_type = match row.get::<_, String>(1)?.as_str() {
-                "Claude" => AgentType::Claude,
-                "Gemini" => AgentType::Gemini,
-                "Codex"  => AgentType::Codex,
-                _        => AgentType::Codexia,
-            };
-            let tokens = match (
-                row.get::<_, Option<i64>>(6)?,
-                row.get::<_, Option<i64>>(7)?,
-                row.get::<_, Option<i64>>(8)?,
-                row.get::<_, Option<i64>>(9)?,
-                row.get::<_, Option<i64>>(10)?,
-            ) {
-                (Some(i), Some(o), Some(c), Some(r), Some(t)) => Some(crate::domain::TokenInfo {
-                    input: i as u64, output: o as u64, cached: c as u64,
-                    cache_creation: 0, reasoning: r as u64, total: t as u64,
-                }),
-                _ => None,
-            };
-            let tool_calls: Vec<String> = row.get::<_, Option<String>>(11)?
-                .and_then(|s| serde_json::from_str(&s).ok())
-                .unwrap_or_default();
-            let created_str: String = row.get(2)?;
-            let modified_str: String = row.get(3)?;
-            Ok(AgentRecord {
-                agent_type,
-                file_path:  row.get(0)?,
-                created_at: created_str.parse().unwrap_or_else(|_| chrono::Utc::now()),
-                modified_at: modified_str.parse().unwrap_or_else(|_| chrono::Utc::now()),
-                file_size:  row.get::<_, i64>(4)? as u64,
-                session_id: row.get(5)?,
-                model:      row.get(12)?,
-                cwd:        row.get(13)?,
-                tokens,
-                tool_calls,
-            })
+    /// Drop everything cached for `agent`'s files: records, their events and
+    /// parse checkpoints. Records of files already found missing are kept,
+    /// since they cannot be parsed again. Returns the number of records
+    /// dropped.
+    pub fn clear_agent(&self, agent: &AgentType) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let agent = agent_type_to_sql(agent);
+        let tx = conn.transaction()?;
+        for table in ["session_events", "parse_checkpoints", "session_content"] {
+            tx.execute(
+                &format!(
+                    "DELETE FROM {} WHERE file_path IN
+                     (SELECT file_path FROM file_cache WHERE agent_type = ?1 AND missing_since IS NULL)",
+                    table
+                ),
+                params![agent],
+            )?;
         }
+        tx.execute(
+            "DELETE FROM parse_checkpoints WHERE file_path IN
+             (SELECT file_path FROM file_records WHERE agent_type = ?1 AND missing_since IS NULL)",
+            params![agent],
+        )?;
+        let dropped = tx.execute(
+            "DELETE FROM file_cache WHERE agent_type = ?1 AND missing_since IS NULL",
+            params![agent],
+        )? + tx.execute(
+            "DELETE FROM file_records WHERE agent_type = ?1 AND missing_since IS NULL",
+            params![agent],
+        )?;
+        tx.commit()?;
+        Ok(dropped)
+    }
+
+    /// Return all cached records, optionally only those active on or after
+    /// `since` (ISO date "YYYY-MM-DD"). A session that started earlier but
+    /// ran past `since` is kept, with its per-day usage clipped to `since`.
+    pub fn get_all_records(&self, since: Option<&str>) -> Result<Vec<AgentRecord>> {
+        let since_date = since.map(|s| s.parse::<NaiveDate>()).transpose()?;
+        let conn = self.conn.lock().unwrap();
+
+        // Timestamps are stored as RFC 3339, so they compare as text with a date
+        let active = "COALESCE(ended_at, modified_at) >= ?1";
+        let sql_with = format!("SELECT {} FROM file_cache WHERE {} ORDER BY started_at", RECORD_COLUMNS, active);
+        let sql_all = format!("SELECT {} FROM file_cache ORDER BY started_at", RECORD_COLUMNS);
+
+        let mut stmt = conn.prepare(if since.is_some() { &sql_with } else { &sql_all })?;
 
         let records: rusqlite::Result<Vec<AgentRecord>> = if let Some(s) = since {
-            stmt.query_map(params![s], parse_row)?.collect()
+            stmt.query_map(params![s], record_from_row)?.collect()
         } else {
-            stmt.query_map([], parse_row)?.collect()
+            stmt.query_map([], record_from_row)?.collect()
         };
-        Ok(records?)
+        let mut records = records?;
+
+        let mut events = if let Some(s) = since {
+            load_events(
+                &conn,
+                &format!("WHERE file_path IN (SELECT file_path FROM file_cache WHERE {})", active),
+                params![s],
+            )?
+        } else {
+            load_events(&conn, "", [])?
+        };
+        for record in &mut records {
+            record.events = events.remove(&record.file_path).unwrap_or_default();
+        }
+        records.extend(load_file_records(&conn, "", [])?.into_iter().filter(|r| {
+            since_date.is_none_or(|since| r.ended_at.date_naive() >= since)
+        }));
+        if let Some(since) = since_date {
+            for record in &mut records {
+                record.days.retain(|day| day.date >= since);
+            }
+        }
+        records.sort_by_key(|r| r.started_at);
+        Ok(records)
     }
 
     pub fn get_cache_stats(&self) -> Result<CacheStats> {
@@ -259,8 +520,383 @@ impl CacheDb {
     }
 }
 
+/// Columns read by [`record_from_row`], in order.
+const RECORD_COLUMNS: &str = "file_path, agent_type, created_at, modified_at, file_size, session_id,
+    tokens_input, tokens_output, tokens_cached, tokens_reasoning, tokens_total, tool_calls, model, cwd,
+    parent_session_id, started_at, ended_at, days, format, git_branch, git_commit, repo_root, repo_remote,
+    cli_version, title, missing_since, tokens_cache_creation";
+
+fn record_from_row(row: &rusqlite::Row) -> rusqlite::Result<AgentRecord> {
+    let agent_type = agent_type_from_sql(&row.get::<_, String>(1)?);
+    let tokens = match (
+        row.get::<_, Option<i64>>(6)?,
+        row.get::<_, Option<i64>>(7)?,
+        row.get::<_, Option<i64>>(8)?,
+        row.get::<_, Option<i64>>(9)?,
+        row.get::<_, Option<i64>>(10)?,
+    ) {
+        (Some(i), Some(o), Some(c), Some(r), Some(t)) => Some(crate::domain::TokenInfo {
+            input: i as u64, output: o as u64, cached: c as u64,
+            cache_creation: row.get::<_, Option<i64>>(26)?.unwrap_or(0) as u64,
+            reasoning: r as u64, total: t as u64,
+        }),
+        _ => None,
+    };
+    let tool_calls: Vec<String> = row.get::<_, Option<String>>(11)?
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    let days: Vec<DayUsage> = row.get::<_, Option<String>>(17)?
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+    let format: Option<LogFormat> = row.get::<_, Option<String>>(18)?
+        .and_then(|s| serde_json::from_str(&s).ok());
+    let created_str: String = row.get(2)?;
+    let modified_str: String = row.get(3)?;
+    let created_at: DateTime<Utc> = created_str.parse().unwrap_or_else(|_| Utc::now());
+    let modified_at: DateTime<Utc> = modified_str.parse().unwrap_or_else(|_| Utc::now());
+    let started_at = row.get::<_, Option<String>>(15)?
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(created_at);
+    let ended_at = row.get::<_, Option<String>>(16)?
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(modified_at);
+    Ok(AgentRecord {
+        agent_type,
+        file_path:  row.get(0)?,
+        created_at,
+        modified_at,
+        started_at,
+        ended_at,
+        file_size:  row.get::<_, i64>(4)? as u64,
+        session_id: row.get(5)?,
+        model:      row.get(12)?,
+        cwd:        row.get(13)?,
+        title:      row.get(24)?,
+        parent_session_id: row.get(14)?,
+        tokens,
+        tool_calls,
+        days,
+        events: Vec::new(),
+        format,
+        git_branch: row.get(19)?,
+        git_commit: row.get(20)?,
+        repo_root:  row.get(21)?,
+        repo_remote: row.get(22)?,
+        cli_version: row.get(23)?,
+        source_missing: row.get::<_, Option<String>>(25)?.is_some(),
+    })
+}
+
+fn mark_missing(conn: &Connection, file_path: &str) -> rusqlite::Result<()> {
+    let now = Utc::now().to_rfc3339();
+    for table in ["file_cache", "file_records"] {
+        conn.execute(
+            &format!("UPDATE {} SET missing_since = ?2 WHERE file_path = ?1", table),
+            params![file_path, now],
+        )?;
+    }
+    Ok(())
+}
+
+fn forget(conn: &Connection, file_path: &str) -> rusqlite::Result<()> {
+    for table in ["session_events", "parse_checkpoints", "session_content", "file_cache", "file_records"] {
+        conn.execute(&format!("DELETE FROM {} WHERE file_path = ?1", table), params![file_path])?;
+    }
+    Ok(())
+}
+
+/// Records of the multi-session files in `file_records` matching `filter`.
+fn load_file_records(
+    conn: &Connection,
+    filter: &str,
+    params: impl rusqlite::Params,
+) -> rusqlite::Result<Vec<AgentRecord>> {
+    let sql = format!("SELECT records, missing_since FROM file_records {}", filter);
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params, |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?.is_some()))
+    })?;
+
+    let mut records = Vec::new();
+    for row in rows {
+        let (json, missing) = row?;
+        let Ok(file_records) = serde_json::from_str::<Vec<AgentRecord>>(&json) else {
+            continue;
+        };
+        records.extend(file_records.into_iter().map(|record| AgentRecord { source_missing: missing, ..record }));
+    }
+    Ok(records)
+}
+
+/// Load events matching `filter`, grouped by file and in logged order.
+fn load_events(
+    conn: &Connection,
+    filter: &str,
+    params: impl rusqlite::Params,
+) -> rusqlite::Result<HashMap<String, Vec<AgentEvent>>> {
+    let sql = format!(
+        "SELECT file_path, kind, timestamp, model, message_id, tool_name, tool_call_id, outcome,
+                tokens_input, tokens_output, tokens_cached, tokens_cache_creation, tokens_reasoning, tokens_total
+         FROM session_events {} ORDER BY file_path, seq",
+        filter
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params, |row| {
+        let kind: String = row.get(1)?;
+        let Ok(kind) = kind.parse::<EventKind>() else {
+            return Ok(None);
+        };
+        let usage = match row.get::<_, Option<i64>>(13)? {
+            Some(total) => Some(TokenInfo {
+                input: row.get::<_, Option<i64>>(8)?.unwrap_or(0) as u64,
+                output: row.get::<_, Option<i64>>(9)?.unwrap_or(0) as u64,
+                cached: row.get::<_, Option<i64>>(10)?.unwrap_or(0) as u64,
+                cache_creation: row.get::<_, Option<i64>>(11)?.unwrap_or(0) as u64,
+                reasoning: row.get::<_, Option<i64>>(12)?.unwrap_or(0) as u64,
+                total: total as u64,
+            }),
+            None => None,
+        };
+        let event = AgentEvent {
+            kind,
+            timestamp: row.get::<_, Option<String>>(2)?.and_then(|s| s.parse().ok()),
+            model: row.get(3)?,
+            usage,
+            message_id: row.get(4)?,
+            tool_name: row.get(5)?,
+            tool_call_id: row.get(6)?,
+            outcome: row.get::<_, Option<String>>(7)?.and_then(|s| s.parse().ok()),
+        };
+        Ok(Some((row.get::<_, String>(0)?, event)))
+    })?;
+
+    let mut events: HashMap<String, Vec<AgentEvent>> = HashMap::new();
+    for row in rows {
+        if let Some((file_path, event)) = row? {
+            events.entry(file_path).or_default().push(event);
+        }
+    }
+    Ok(events)
+}
+
+/// Custom agents are stored with a `custom:` prefix so that a built-in name
+/// can never be confused with a registered scanner of the same name.
+fn agent_type_to_sql(agent_type: &AgentType) -> String {
+    match agent_type {
+        AgentType::Custom(name) => format!("custom:{}", name),
+        other => other.to_string(),
+    }
+}
+
+fn agent_type_from_sql(value: &str) -> AgentType {
+    match value {
+        "Claude" => AgentType::Claude,
+        "Gemini" => AgentType::Gemini,
+        "Codex" => AgentType::Codex,
+        "Codexia" => AgentType::Codexia,
+        "Aider" => AgentType::Aider,
+        "OpenCode" => AgentType::OpenCode,
+        "Crush" => AgentType::Crush,
+        other => match other.strip_prefix("custom:") {
+            Some(name) if !name.is_empty() => AgentType::Custom(name.to_string()),
+            _ => {
+                tracing::warn!("Unrecognised agent type in cache: {:?}", other);
+                AgentType::Unknown
+            }
+        },
+    }
+}
+
+/// Environment variable choosing what happens to cached records whose
+/// session file was deleted or moved: `archive` (default) or `prune`.
+pub const MISSING_FILES_ENV: &str = "AGENT_INSIGHTS_MISSING_FILES";
+
+/// Environment variable that, set to `1` or `true`, keeps a compressed
+/// copy of every parsed session file in the cache.
+pub const KEEP_CONTENT_ENV: &str = "AGENT_INSIGHTS_KEEP_CONTENT";
+
+/// Whether `AGENT_INSIGHTS_KEEP_CONTENT` asks for session files to be kept.
+pub fn keep_content_from_env() -> bool {
+    std::env::var(KEEP_CONTENT_ENV).is_ok_and(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+}
+
+/// What to do with cached records whose session file no longer exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingFiles {
+    /// Keep them, flagged as [`source_missing`], since agents delete their
+    /// own old logs and the cache may be the only history left.
+    ///
+    /// [`source_missing`]: AgentRecord::source_missing
+    #[default]
+    Archive,
+    /// Delete them along with their events and checkpoints.
+    Prune,
+}
+
+impl MissingFiles {
+    /// The policy set in `AGENT_INSIGHTS_MISSING_FILES`. Unset or
+    /// unrecognised values archive.
+    pub fn from_env() -> Self {
+        match std::env::var(MISSING_FILES_ENV).as_deref() {
+            Ok("prune") => Self::Prune,
+            Ok("archive") | Err(_) => Self::Archive,
+            Ok(other) => {
+                tracing::warn!("Unrecognised {} value {:?}; archiving", MISSING_FILES_ENV, other);
+                Self::Archive
+            }
+        }
+    }
+}
+
+/// How far a session file has been parsed, and the parser's state at that
+/// point, so that appended lines can be parsed without starting over.
+#[derive(Debug, Clone)]
+pub struct ParseCheckpoint {
+    /// Offset just past the last complete line parsed.
+    pub offset: u64,
+    /// Hash of the file's leading bytes up to `offset`, to notice a file
+    /// that was rewritten rather than appended to.
+    pub prefix_hash: u64,
+    /// Parser state, serialized by the scanner that owns the file.
+    pub state: String,
+}
+
 #[derive(Debug)]
 pub struct CacheStats {
     pub total_entries: usize,
     pub entries_by_agent: std::collections::HashMap<String, usize>,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn record(path: &Path) -> AgentRecord {
+        let now = Utc::now();
+        AgentRecord {
+            agent_type: AgentType::Codex,
+            file_path: path.to_string_lossy().to_string(),
+            created_at: now,
+            modified_at: now,
+            started_at: now,
+            ended_at: now,
+            file_size: 1,
+            session_id: None,
+            parent_session_id: None,
+            model: None,
+            cwd: None,
+            title: None,
+            tokens: None,
+            tool_calls: Vec::new(),
+            days: Vec::new(),
+            events: Vec::new(),
+            format: None,
+            cli_version: None,
+            git_branch: None,
+            git_commit: None,
+            repo_root: None,
+            repo_remote: None,
+            source_missing: false,
+        }
+    }
+
+    #[test]
+    fn records_of_deleted_files_are_archived_or_pruned() {
+        let dir = std::env::temp_dir().join(format!("agent-insights-missing-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let db = CacheDb::new(Some(dir.join("cache.db"))).unwrap();
+        let kept = dir.join("kept.jsonl");
+        let archived = dir.join("archived.jsonl");
+        let pruned = dir.join("pruned.jsonl");
+        fs::write(&kept, "").unwrap();
+        for path in [&kept, &archived, &pruned] {
+            db.cache_record(&record(path), 1).unwrap();
+        }
+
+        assert_eq!(db.reconcile_missing(MissingFiles::Archive).unwrap(), 2);
+        // Already archived records are not counted again
+        fs::write(&pruned, "").unwrap();
+        db.cache_record(&record(&pruned), 1).unwrap();
+        fs::remove_file(&pruned).unwrap();
+        assert_eq!(db.reconcile_missing(MissingFiles::Prune).unwrap(), 1);
+        assert_eq!(db.clear_agent(&AgentType::Codex).unwrap(), 1);
+
+        let records = db.get_all_records(None).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].file_path, archived.to_string_lossy());
+        assert!(records[0].source_missing);
+    }
+
+    #[test]
+    fn archived_sessions_keep_their_stats_and_content() {
+        let dir = std::env::temp_dir().join(format!("agent-insights-archive-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let db = CacheDb::new(Some(dir.join("cache.db"))).unwrap().with_content(true);
+        let path = dir.join("gone.jsonl");
+        fs::write(&path, "{\"line\":1}\n".repeat(100)).unwrap();
+        let mut gone = record(&path);
+        gone.tokens = Some(TokenInfo { input: 1, output: 2, cached: 3, cache_creation: 4, reasoning: 0, total: 10 });
+        db.cache_record(&gone, 1).unwrap();
+        db.store_content(&gone.file_path, &gone.modified_at, &path).unwrap();
+        fs::remove_file(&path).unwrap();
+        db.reconcile_missing(MissingFiles::Archive).unwrap();
+
+        let archived = db.get_archived_records(Some(&AgentType::Codex)).unwrap();
+        let content = db.get_content(&gone.file_path).unwrap();
+        let other = db.get_archived_records(Some(&AgentType::Claude)).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(archived.len(), 1);
+        assert!(archived[0].source_missing);
+        assert_eq!(archived[0].tokens.as_ref().unwrap().cache_creation, 4);
+        assert_eq!(content.unwrap(), "{\"line\":1}\n".repeat(100).into_bytes());
+        assert!(other.is_empty());
+    }
+
+    #[test]
+    fn sessions_running_past_the_cutoff_are_kept_and_clipped() {
+        let dir = std::env::temp_dir().join(format!("agent-insights-since-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let db = CacheDb::new(Some(dir.join("cache.db"))).unwrap();
+        let at = |s: &str| s.parse::<DateTime<Utc>>().unwrap();
+        let day = |date: &str, messages| DayUsage { date: date.parse().unwrap(), messages, tokens: 0, tool_calls: 0 };
+        let session = |name: &str, started: &str, ended: &str| {
+            let mut record = record(&dir.join(name));
+            record.started_at = at(started);
+            record.ended_at = at(ended);
+            record.days = vec![day(&started[..10], 1), day(&ended[..10], 2)];
+            record
+        };
+        let spanning = session("spanning.jsonl", "2025-03-01T23:00:00Z", "2025-03-02T01:00:00Z");
+        let before = session("before.jsonl", "2025-02-27T10:00:00Z", "2025-02-28T10:00:00Z");
+        let after = session("after.jsonl", "2025-03-03T10:00:00Z", "2025-03-03T11:00:00Z");
+        for record in [&spanning, &before, &after] {
+            db.cache_record(record, 1).unwrap();
+        }
+        // Files holding several sessions are filtered the same way
+        let transcript = dir.join("transcript.md").to_string_lossy().to_string();
+        let mut several = vec![
+            session("transcript.md", "2025-02-20T10:00:00Z", "2025-02-20T11:00:00Z"),
+            session("transcript.md", "2025-03-01T22:00:00Z", "2025-03-02T02:00:00Z"),
+        ];
+        several.iter_mut().for_each(|r| r.agent_type = AgentType::Aider);
+        db.cache_records(&transcript, &AgentType::Aider, &at("2025-03-02T02:00:00Z"), &several, 1).unwrap();
+
+        let records = db.get_all_records(Some("2025-03-02")).unwrap();
+        let all = db.get_all_records(None).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let kept: Vec<_> = records.iter()
+            .map(|r| (r.started_at, r.days.iter().map(|d| d.date.to_string()).collect::<Vec<_>>()))
+            .collect();
+        assert_eq!(kept, vec![
+            (at("2025-03-01T22:00:00Z"), vec!["2025-03-02".to_string()]),
+            (at("2025-03-01T23:00:00Z"), vec!["2025-03-02".to_string()]),
+            (at("2025-03-03T10:00:00Z"), vec!["2025-03-03".to_string(), "2025-03-03".to_string()]),
+        ]);
+        assert_eq!(all.len(), 5);
+        assert_eq!(all[0].days.len(), 2);
+    }
+}
@@ -0,0 +1,330 @@
+//! Versioned schema of the cache database.
+//!
+//! Each migration brings the schema from one version to the next and runs
+//! in its own transaction. The version reached is stored in
+//! `schema_version`, so an existing cache is upgraded in place instead of
+//! having to be deleted. To change the schema, append a migration; never
+//! edit one that has shipped.
+
+use anyhow::Result;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+
+type Migration = fn(&Transaction) -> rusqlite::Result<()>;
+
+/// Migrations in order; the schema version is the number applied.
+const MIGRATIONS: &[Migration] = &[
+    create_file_cache,
+    add_record_columns,
+    create_session_events,
+    create_parse_checkpoints,
+    add_parser_versions,
+    add_missing_since,
+    add_cache_creation_tokens,
+    create_session_content,
+    create_file_records,
+];
+
+/// Schema version of a fully migrated database.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// Apply every migration newer than the database's version.
+pub fn migrate(conn: &mut Connection) -> Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)",
+        [],
+    )?;
+    let current = schema_version(conn)?;
+    if current > SCHEMA_VERSION {
+        tracing::warn!(
+            "Cache schema version {} is newer than this build's {}; using it as is",
+            current,
+            SCHEMA_VERSION
+        );
+        return Ok(());
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        let version = index as i64 + 1;
+        let tx = conn.transaction()?;
+        migration(&tx)?;
+        tx.execute("DELETE FROM schema_version", [])?;
+        tx.execute("INSERT INTO schema_version (version) VALUES (?1)", params![version])?;
+        tx.commit()?;
+        tracing::debug!("Migrated cache schema to version {}", version);
+    }
+    Ok(())
+}
+
+/// Version recorded in the database; 0 before any migration has run.
+pub fn schema_version(conn: &Connection) -> Result<i64> {
+    let version = conn
+        .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+        .optional()?;
+    Ok(version.unwrap_or(0))
+}
+
+/// The table as first released. Databases from before versioning already
+/// have it and pass through unchanged.
+fn create_file_cache(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS file_cache (
+            file_path TEXT PRIMARY KEY,
+            agent_type TEXT NOT NULL,
+            created_at TEXT NOT NULL,
+            modified_at TEXT NOT NULL,
+            file_size INTEGER NOT NULL,
+            session_id TEXT,
+            tokens_input INTEGER,
+            tokens_output INTEGER,
+            tokens_cached INTEGER,
+            tokens_reasoning INTEGER,
+            tokens_total INTEGER,
+            tool_calls TEXT,
+            cached_at TEXT NOT NULL,
+            cwd TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Columns added to `file_cache` before versioning, including `model`,
+/// which was written but never created, and `parent_session_id`, which
+/// was once only added to `CREATE TABLE` and so is missing from databases
+/// created before it. Unversioned databases may have any subset of them,
+/// so each is added only if missing.
+fn add_record_columns(tx: &Transaction) -> rusqlite::Result<()> {
+    add_missing_columns(
+        tx,
+        "file_cache",
+        &[
+            ("model", "TEXT"),
+            ("parent_session_id", "TEXT"),
+            ("started_at", "TEXT"),
+            ("ended_at", "TEXT"),
+            ("days", "TEXT"),
+            ("format", "TEXT"),
+            ("git_branch", "TEXT"),
+            ("git_commit", "TEXT"),
+            ("repo_root", "TEXT"),
+            ("repo_remote", "TEXT"),
+            ("cli_version", "TEXT"),
+            ("title", "TEXT"),
+        ],
+    )
+}
+
+fn create_session_events(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS session_events (
+            file_path TEXT NOT NULL,
+            seq INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            timestamp TEXT,
+            model TEXT,
+            message_id TEXT,
+            tool_name TEXT,
+            tool_call_id TEXT,
+            outcome TEXT,
+            tokens_input INTEGER,
+            tokens_output INTEGER,
+            tokens_cached INTEGER,
+            tokens_cache_creation INTEGER,
+            tokens_reasoning INTEGER,
+            tokens_total INTEGER,
+            PRIMARY KEY (file_path, seq)
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn create_parse_checkpoints(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS parse_checkpoints (
+            file_path TEXT PRIMARY KEY,
+            byte_offset INTEGER NOT NULL,
+            prefix_hash INTEGER NOT NULL,
+            state TEXT NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Version of the parser that produced each row. Rows written before it
+/// was tracked are version 0, older than any parser, so they are re-parsed.
+fn add_parser_versions(tx: &Transaction) -> rusqlite::Result<()> {
+    add_missing_columns(tx, "file_cache", &[("parser_version", "INTEGER NOT NULL DEFAULT 0")])?;
+    add_missing_columns(tx, "parse_checkpoints", &[("parser_version", "INTEGER NOT NULL DEFAULT 0")])
+}
+
+/// When a cached file was first found missing from disk; NULL while it exists.
+fn add_missing_since(tx: &Transaction) -> rusqlite::Result<()> {
+    add_missing_columns(tx, "file_cache", &[("missing_since", "TEXT")])
+}
+
+/// Cache-creation tokens were dropped from cached records, undercounting
+/// Claude sessions served from the cache.
+fn add_cache_creation_tokens(tx: &Transaction) -> rusqlite::Result<()> {
+    add_missing_columns(tx, "file_cache", &[("tokens_cache_creation", "INTEGER")])
+}
+
+/// Compressed raw session files, kept on request so archived sessions can
+/// be parsed again.
+fn create_session_content(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS session_content (
+            file_path TEXT PRIMARY KEY,
+            modified_at TEXT NOT NULL,
+            content BLOB NOT NULL
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Records of files holding several sessions, such as Aider transcripts
+/// and Crush databases, stored together as JSON since `file_cache` has one
+/// row per file.
+fn create_file_records(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS file_records (
+            file_path TEXT PRIMARY KEY,
+            agent_type TEXT NOT NULL,
+            modified_at TEXT NOT NULL,
+            parser_version INTEGER NOT NULL,
+            records TEXT NOT NULL,
+            cached_at TEXT NOT NULL,
+            missing_since TEXT
+        )",
+        [],
+    )?;
+    Ok(())
+}
+
+fn add_missing_columns(tx: &Transaction, table: &str, columns: &[(&str, &str)]) -> rusqlite::Result<()> {
+    let existing: Vec<String> = tx
+        .prepare(&format!("PRAGMA table_info({})", table))?
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<_>>()?;
+    for (name, sql_type) in columns {
+        if !existing.iter().any(|c| c == name) {
+            tx.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, name, sql_type), [])?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::CacheDb;
+    use crate::domain::{AgentRecord, AgentType};
+    use chrono::Utc;
+    use std::path::PathBuf;
+
+    fn temp_db(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("agent-insights-{}-{}.db", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn record(path: &str) -> AgentRecord {
+        let now = Utc::now();
+        AgentRecord {
+            agent_type: AgentType::Claude,
+            file_path: path.to_string(),
+            created_at: now,
+            modified_at: now,
+            started_at: now,
+            ended_at: now,
+            file_size: 10,
+            session_id: Some("s1".to_string()),
+            parent_session_id: None,
+            model: Some("claude-opus-4".to_string()),
+            cwd: None,
+            title: Some("Fix login".to_string()),
+            tokens: None,
+            tool_calls: vec!["Bash".to_string()],
+            days: Vec::new(),
+            events: Vec::new(),
+            format: None,
+            cli_version: Some("2.0.1".to_string()),
+            git_branch: None,
+            git_commit: None,
+            repo_root: None,
+            repo_remote: None,
+            source_missing: false,
+        }
+    }
+
+    /// Open a database written by an older build, then read and write it.
+    fn upgrade(name: &str, legacy_schema: &str) {
+        let path = temp_db(name);
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(legacy_schema).unwrap();
+        }
+
+        let db = CacheDb::new(Some(path.clone())).unwrap();
+        let old = db.get_all_records(None).unwrap();
+        let fresh = AgentRecord { parent_session_id: Some("parent".to_string()), ..record("/sessions/new.jsonl") };
+        db.cache_record(&fresh, 1).unwrap();
+        let cached = db.get_cached_record(&fresh.file_path, &fresh.modified_at, 1).unwrap().unwrap();
+        let version = schema_version(&Connection::open(&path).unwrap()).unwrap();
+        drop(db);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(version, SCHEMA_VERSION);
+        assert_eq!(old.len(), 1);
+        assert_eq!(old[0].session_id.as_deref(), Some("old"));
+        assert_eq!(cached.model.as_deref(), Some("claude-opus-4"));
+        assert_eq!(cached.title.as_deref(), Some("Fix login"));
+        assert_eq!(cached.parent_session_id.as_deref(), Some("parent"));
+    }
+
+    #[test]
+    fn upgrades_the_original_unversioned_schema() {
+        upgrade(
+            "v0",
+            "CREATE TABLE file_cache (
+                file_path TEXT PRIMARY KEY, agent_type TEXT NOT NULL, created_at TEXT NOT NULL,
+                modified_at TEXT NOT NULL, file_size INTEGER NOT NULL, session_id TEXT,
+                tokens_input INTEGER, tokens_output INTEGER, tokens_cached INTEGER,
+                tokens_reasoning INTEGER, tokens_total INTEGER, tool_calls TEXT,
+                cached_at TEXT NOT NULL, cwd TEXT);
+             INSERT INTO file_cache VALUES ('/sessions/old.jsonl', 'Claude', '2025-01-01T00:00:00+00:00',
+                '2025-01-01T00:00:00+00:00', 5, 'old', 1, 2, 0, 0, 3, '[]', '2025-01-01T00:00:00+00:00', NULL);",
+        );
+    }
+
+    #[test]
+    fn upgrades_an_unversioned_schema_with_some_columns_added() {
+        upgrade(
+            "v0-partial",
+            "CREATE TABLE file_cache (
+                file_path TEXT PRIMARY KEY, agent_type TEXT NOT NULL, created_at TEXT NOT NULL,
+                modified_at TEXT NOT NULL, file_size INTEGER NOT NULL, session_id TEXT,
+                tokens_input INTEGER, tokens_output INTEGER, tokens_cached INTEGER,
+                tokens_reasoning INTEGER, tokens_total INTEGER, tool_calls TEXT,
+                cached_at TEXT NOT NULL, cwd TEXT, parent_session_id TEXT, started_at TEXT, ended_at TEXT);
+             CREATE TABLE parse_checkpoints (file_path TEXT PRIMARY KEY, byte_offset INTEGER NOT NULL,
+                prefix_hash INTEGER NOT NULL, state TEXT NOT NULL);
+             INSERT INTO file_cache (file_path, agent_type, created_at, modified_at, file_size, session_id,
+                cached_at, started_at)
+             VALUES ('/sessions/old.jsonl', 'Claude', '2025-01-01T00:00:00+00:00', '2025-01-01T00:00:00+00:00',
+                5, 'old', '2025-01-01T00:00:00+00:00', '2025-01-01T00:00:00+00:00');",
+        );
+    }
+
+    #[test]
+    fn reopening_a_current_database_is_a_no_op() {
+        let path = temp_db("current");
+        drop(CacheDb::new(Some(path.clone())).unwrap());
+        drop(CacheDb::new(Some(path.clone())).unwrap());
+        let version = schema_version(&Connection::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+    }
+}
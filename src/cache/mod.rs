@@ -1,5 +1,7 @@
 #[cfg(feature = "cache")]
 pub mod db;
+#[cfg(feature = "cache")]
+pub mod migrations;
 
 #[cfg(feature = "cache")]
-pub use db::CacheDb;
+pub use db::{keep_content_from_env, CacheDb, MissingFiles, ParseCheckpoint};
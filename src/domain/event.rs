@@ -0,0 +1,181 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::TokenInfo;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    UserPrompt,
+    AssistantMessage,
+    ToolCall,
+    ToolResult,
+    /// Usage reported on its own rather than attached to a message.
+    TokenUsage,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::UserPrompt => "user_prompt",
+            EventKind::AssistantMessage => "assistant_message",
+            EventKind::ToolCall => "tool_call",
+            EventKind::ToolResult => "tool_result",
+            EventKind::TokenUsage => "token_usage",
+        }
+    }
+}
+
+impl fmt::Display for EventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for EventKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "user_prompt" => Ok(EventKind::UserPrompt),
+            "assistant_message" => Ok(EventKind::AssistantMessage),
+            "tool_call" => Ok(EventKind::ToolCall),
+            "tool_result" => Ok(EventKind::ToolResult),
+            "token_usage" => Ok(EventKind::TokenUsage),
+            other => Err(format!("unknown event kind: {}", other)),
+        }
+    }
+}
+
+/// How a tool call ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolOutcome {
+    Success,
+    Error,
+    /// The user declined to run the call.
+    Rejected,
+}
+
+impl ToolOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ToolOutcome::Success => "success",
+            ToolOutcome::Error => "error",
+            ToolOutcome::Rejected => "rejected",
+        }
+    }
+}
+
+impl FromStr for ToolOutcome {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "success" => Ok(ToolOutcome::Success),
+            "error" => Ok(ToolOutcome::Error),
+            "rejected" => Ok(ToolOutcome::Rejected),
+            other => Err(format!("unknown tool outcome: {}", other)),
+        }
+    }
+}
+
+/// One normalized step of a session, in the order it was logged.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentEvent {
+    pub kind: EventKind,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub model: Option<String>,
+    /// Tokens billed for this event. On assistant messages this is the
+    /// message's final usage, counted once however many lines it streamed over.
+    pub usage: Option<TokenInfo>,
+    /// Provider message id, for assistant messages.
+    pub message_id: Option<String>,
+    /// Tool name, for tool calls.
+    pub tool_name: Option<String>,
+    /// Links a tool result to the call it answers.
+    pub tool_call_id: Option<String>,
+    /// How a tool result ended; `None` when the log doesn't say.
+    pub outcome: Option<ToolOutcome>,
+}
+
+impl AgentEvent {
+    pub fn new(kind: EventKind, timestamp: Option<DateTime<Utc>>) -> Self {
+        Self {
+            kind,
+            timestamp,
+            model: None,
+            usage: None,
+            message_id: None,
+            tool_name: None,
+            tool_call_id: None,
+            outcome: None,
+        }
+    }
+
+    pub fn with_model(mut self, model: Option<&str>) -> Self {
+        self.model = model.map(|m| m.to_string());
+        self
+    }
+
+    pub fn with_usage(mut self, usage: Option<TokenInfo>) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn with_message_id(mut self, id: Option<&str>) -> Self {
+        self.message_id = id.map(|s| s.to_string());
+        self
+    }
+
+    pub fn with_tool(mut self, name: Option<&str>, call_id: Option<&str>) -> Self {
+        self.tool_name = name.map(|s| s.to_string());
+        self.tool_call_id = call_id.map(|s| s.to_string());
+        self
+    }
+
+    pub fn with_outcome(mut self, outcome: Option<ToolOutcome>) -> Self {
+        self.outcome = outcome;
+        self
+    }
+}
+
+/// A tool call paired with its result.
+#[derive(Debug, Clone)]
+pub struct ToolInvocation {
+    pub tool_name: String,
+    /// `None` when no result was logged, e.g. the session was interrupted.
+    pub outcome: Option<ToolOutcome>,
+    /// Wall-clock time from call to result, when both are timestamped.
+    pub duration_ms: Option<u64>,
+}
+
+/// Pair each tool call in `events` with the result carrying its call id.
+pub fn tool_invocations(events: &[AgentEvent]) -> Vec<ToolInvocation> {
+    let mut results: HashMap<&str, &AgentEvent> = HashMap::new();
+    for event in events.iter().filter(|e| e.kind == EventKind::ToolResult) {
+        if let Some(id) = event.tool_call_id.as_deref() {
+            results.entry(id).or_insert(event);
+        }
+    }
+
+    events
+        .iter()
+        .filter(|e| e.kind == EventKind::ToolCall)
+        .filter_map(|call| {
+            let tool_name = call.tool_name.clone()?;
+            let result = call.tool_call_id.as_deref().and_then(|id| results.get(id));
+            let duration_ms = result
+                .and_then(|r| Some(r.timestamp? - call.timestamp?))
+                .and_then(|d| u64::try_from(d.num_milliseconds()).ok());
+            Some(ToolInvocation {
+                tool_name,
+                outcome: result.and_then(|r| r.outcome),
+                duration_ms,
+            })
+        })
+        .collect()
+}
@@ -2,17 +2,56 @@ use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Activity on one day, with multi-day sessions split across the days
+/// they have events on.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DayActivity {
     pub date: String,
-    pub count: usize,
+    /// Sessions with any activity on this day.
+    pub sessions: usize,
+    pub messages: usize,
+    pub tokens: u64,
+    pub tool_calls: usize,
+    /// Size of the session logs started on this day.
     pub size: u64,
 }
 
+impl DayActivity {
+    pub fn new(date: NaiveDate) -> Self {
+        Self {
+            date: date.format("%Y-%m-%d").to_string(),
+            sessions: 0,
+            messages: 0,
+            tokens: 0,
+            tool_calls: 0,
+            size: 0,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCallStats {
     pub tool_name: String,
     pub count: usize,
+    pub error_count: usize,
+    /// Calls the user declined to run.
+    pub rejected_count: usize,
+    /// Median call duration, over calls whose result was timestamped.
+    pub p50_duration_ms: Option<u64>,
+    pub p95_duration_ms: Option<u64>,
+}
+
+/// Usage and tool reliability of the sessions written by one CLI version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionStats {
+    /// CLI version, or `"unknown"` for logs that don't record one.
+    pub version: String,
+    pub sessions: usize,
+    pub tokens: u64,
+    pub tool_calls: usize,
+    pub tool_errors: usize,
+    /// `tool_errors / tool_calls`, or 0 without tool calls.
+    pub tool_error_rate: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +69,7 @@ pub struct TokenStats {
 pub struct HeatmapData {
     pub agent: String,
     pub data: Vec<DayActivity>,
+    /// Most sessions active on a single day.
     pub max_count: usize,
     pub total_files: usize,
     pub total_size: u64,
@@ -37,29 +77,25 @@ pub struct HeatmapData {
     pub token_stats: TokenStats,
     /// Unique model names seen across all records, sorted by frequency
     pub models: Vec<String>,
+    /// Breakdown by CLI version, newest first.
+    pub versions: Vec<VersionStats>,
 }
 
 impl HeatmapData {
-    pub fn from_counts(
+    pub fn from_days(
         agent: String,
-        day_counts: HashMap<NaiveDate, (usize, u64)>,
+        days: HashMap<NaiveDate, DayActivity>,
+        total_files: usize,
         tool_calls: Vec<ToolCallStats>,
         token_stats: TokenStats,
         models: Vec<String>,
+        versions: Vec<VersionStats>,
     ) -> Self {
-        let mut data: Vec<DayActivity> = day_counts
-            .into_iter()
-            .map(|(date, (count, size))| DayActivity {
-                date: date.format("%Y-%m-%d").to_string(),
-                count,
-                size,
-            })
-            .collect();
+        let mut data: Vec<DayActivity> = days.into_values().collect();
 
         data.sort_by(|a, b| a.date.cmp(&b.date));
 
-        let max_count = data.iter().map(|d| d.count).max().unwrap_or(0);
-        let total_files = data.iter().map(|d| d.count).sum();
+        let max_count = data.iter().map(|d| d.sessions).max().unwrap_or(0);
         let total_size = data.iter().map(|d| d.size).sum();
 
         HeatmapData {
@@ -71,6 +107,7 @@ impl HeatmapData {
             tool_calls,
             token_stats,
             models,
+            versions,
         }
     }
 }
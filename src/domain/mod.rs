@@ -1,5 +1,11 @@
 pub mod record;
 pub mod heatmap;
+pub mod event;
+pub mod report;
+pub mod session;
 
 pub use record::*;
 pub use heatmap::*;
+pub use event::*;
+pub use report::*;
+pub use session::*;
@@ -1,12 +1,45 @@
 use chrono::{DateTime, Utc, NaiveDate};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use super::AgentEvent;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum AgentType {
     Claude,
     Codex,
     Gemini,
     Codexia,
+    Aider,
+    OpenCode,
+    Crush,
+    /// An agent contributed by a scanner registered outside this crate.
+    Custom(String),
+    /// A stored record whose agent could not be identified.
+    Unknown,
+}
+
+impl AgentType {
+    /// Lowercase identifier used in API routes, e.g. `/api/heatmap/claude`.
+    pub fn id(&self) -> String {
+        self.to_string().to_lowercase()
+    }
+}
+
+impl fmt::Display for AgentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentType::Claude => f.write_str("Claude"),
+            AgentType::Codex => f.write_str("Codex"),
+            AgentType::Gemini => f.write_str("Gemini"),
+            AgentType::Codexia => f.write_str("Codexia"),
+            AgentType::Aider => f.write_str("Aider"),
+            AgentType::OpenCode => f.write_str("OpenCode"),
+            AgentType::Crush => f.write_str("Crush"),
+            AgentType::Custom(name) => f.write_str(name),
+            AgentType::Unknown => f.write_str("Unknown"),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,12 +48,114 @@ pub struct AgentRecord {
     pub file_path: String,
     pub created_at: DateTime<Utc>,
     pub modified_at: DateTime<Utc>,
+    /// First event recorded in the session. Falls back to `created_at` when
+    /// the log carries no timestamps.
+    pub started_at: DateTime<Utc>,
+    /// Last event recorded in the session. Falls back to `modified_at`.
+    pub ended_at: DateTime<Utc>,
     pub file_size: u64,
     pub session_id: Option<String>,
+    /// Session that spawned this one, for sub-agent (sidechain) sessions.
+    /// Their usage rolls up into the parent's agent.
+    pub parent_session_id: Option<String>,
     pub model: Option<String>,
     pub cwd: Option<String>,
+    /// What the session was about: the agent's own summary when it writes
+    /// one, otherwise the first user prompt. Truncated, secrets redacted.
+    #[serde(default)]
+    pub title: Option<String>,
     pub tokens: Option<TokenInfo>,
     pub tool_calls: Vec<String>,
+    /// Per-day breakdown for sessions whose events carry timestamps. Empty
+    /// when unknown, in which case everything counts on [`AgentRecord::date`].
+    #[serde(default)]
+    pub days: Vec<DayUsage>,
+    /// Normalized per-message events, for parsers that can produce them.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<AgentEvent>,
+    /// On-disk format the session was parsed as, for parsers that detect it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<LogFormat>,
+    /// Version of the agent CLI that wrote the session.
+    #[serde(default)]
+    pub cli_version: Option<String>,
+    /// Branch the session ran on, from the log or else the checkout on disk.
+    #[serde(default)]
+    pub git_branch: Option<String>,
+    /// Commit the session started from, when the log records it or the
+    /// checkout is still on the same branch.
+    #[serde(default)]
+    pub git_commit: Option<String>,
+    /// Root of the git checkout enclosing `cwd`.
+    #[serde(default)]
+    pub repo_root: Option<String>,
+    /// Remote URL of the repository, identifying it across clones.
+    #[serde(default)]
+    pub repo_remote: Option<String>,
+    /// The session file no longer exists; the record is kept from the
+    /// cache as history.
+    #[serde(default)]
+    pub source_missing: bool,
+}
+
+/// The log format a session file was recognised as.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogFormat {
+    /// Extractor the file was routed to, e.g. `"rollout"` or `"checkpoint"`.
+    pub schema: String,
+    /// Why the file may not have been read correctly: a version newer than
+    /// the parser knows, or a shape it only partly understood.
+    pub drift: Option<String>,
+    /// Lines skipped because they could not be parsed.
+    #[serde(default)]
+    pub invalid_lines: usize,
+}
+
+impl LogFormat {
+    pub fn new(schema: &str) -> Self {
+        Self {
+            schema: schema.to_string(),
+            drift: None,
+            invalid_lines: 0,
+        }
+    }
+
+    /// Record a reason to doubt the parse, keeping the first one found.
+    pub fn flag(&mut self, reason: impl Into<String>) {
+        self.drift.get_or_insert_with(|| reason.into());
+    }
+
+    /// Flag the file when the `version` of `tool` that wrote it has a major
+    /// component above `newest_major`, the newest the parser was written
+    /// against.
+    pub fn check_major(&mut self, tool: &str, version: Option<&str>, newest_major: u64) {
+        if let Some(version) = version
+            && major_version(version).is_some_and(|major| major > newest_major)
+        {
+            self.flag(format!(
+                "{} {} is newer than the formats this parser knows (up to {}.x)",
+                tool, version, newest_major
+            ));
+        }
+    }
+}
+
+/// Numeric components of a version string: `"2.0.14-beta"` gives
+/// `[2, 0, 14]`. Orders versions sensibly where string comparison doesn't.
+pub fn version_parts(version: &str) -> Vec<u64> {
+    version
+        .trim_start_matches('v')
+        .split('.')
+        .map_while(|part| {
+            let digits: String = part.chars().take_while(|c| c.is_ascii_digit()).collect();
+            digits.parse().ok()
+        })
+        .collect()
+}
+
+/// Leading number of a version string such as `"2.0.14"` or `"v0.46.0"`.
+pub fn major_version(version: &str) -> Option<u64> {
+    version_parts(version).first().copied()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,11 +165,58 @@ pub struct TokenInfo {
     pub cached: u64,
     pub cache_creation: u64,
     pub reasoning: u64,
+    /// Tokens the session used, counted the way its agent counts them:
+    /// the reported total where the log has one, otherwise the sum of the
+    /// parts the agent bills. Aggregates add these up rather than
+    /// recomputing them from the parts.
     pub total: u64,
 }
 
+/// What a session did on one UTC calendar day.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DayUsage {
+    pub date: NaiveDate,
+    pub messages: usize,
+    pub tokens: u64,
+    pub tool_calls: usize,
+}
+
+impl DayUsage {
+    pub fn new(date: NaiveDate) -> Self {
+        Self { date, ..Default::default() }
+    }
+
+    pub fn add(&mut self, other: &DayUsage) {
+        self.messages += other.messages;
+        self.tokens = self.tokens.saturating_add(other.tokens);
+        self.tool_calls += other.tool_calls;
+    }
+}
+
 impl AgentRecord {
+    /// Day the session's activity is attributed to.
     pub fn date(&self) -> NaiveDate {
-        self.created_at.date_naive()
+        self.started_at.date_naive()
+    }
+
+    /// Whether this is a sub-agent session spawned by another session.
+    pub fn is_subagent(&self) -> bool {
+        self.parent_session_id.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_order_by_their_numeric_parts() {
+        assert_eq!(version_parts("2.0.14-beta"), vec![2, 0, 14]);
+        assert_eq!(version_parts("v0.46.0"), vec![0, 46, 0]);
+        assert_eq!(version_parts("unknown"), Vec::<u64>::new());
+        assert!(version_parts("2.0.14") > version_parts("2.0.9"));
+        assert!(version_parts("2.1") > version_parts("2.0.14"));
+        assert!(version_parts("0.9.0") > version_parts("unknown"));
+        assert_eq!(major_version("v3.1.0"), Some(3));
     }
 }
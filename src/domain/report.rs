@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+
+/// A file that could not be read or parsed, and why.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScanFailure {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+/// What one agent's scan did with the files it found.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScanReport {
+    pub agent: String,
+    /// Matching files found under the scanner's roots.
+    pub files_seen: usize,
+    /// Files parsed from disk.
+    pub parsed: usize,
+    /// Files answered from the cache without parsing.
+    pub cached: usize,
+    /// Files parsed because the cache held no current record for them.
+    /// Always 0 for scans without a cache.
+    pub cache_misses: usize,
+    /// Files that parsed but held no sessions.
+    pub skipped: usize,
+    /// Files that failed to parse, plus directory entries that could not be read.
+    pub failed: usize,
+    pub records: usize,
+    /// Lines skipped across all files because they could not be parsed.
+    pub invalid_lines: usize,
+    pub failures: Vec<ScanFailure>,
+    /// Files that parsed but whose format the parser did not fully
+    /// recognise, e.g. written by a newer version of the agent.
+    pub format_drift: Vec<ScanFailure>,
+}
+
+impl ScanReport {
+    pub fn new(agent: impl Into<String>) -> Self {
+        Self {
+            agent: agent.into(),
+            ..Self::default()
+        }
+    }
+
+    pub fn fail(&mut self, path: PathBuf, reason: impl Into<String>) {
+        self.failed += 1;
+        self.failures.push(ScanFailure {
+            path,
+            reason: reason.into(),
+        });
+    }
+}
+
+impl fmt::Display for ScanReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} files, {} parsed, {} cached, {} skipped, {} failed, {} records",
+            self.agent, self.files_seen, self.parsed, self.cached, self.skipped, self.failed, self.records
+        )?;
+        if self.cache_misses > 0 {
+            write!(f, ", {} cache misses", self.cache_misses)?;
+        }
+        if self.invalid_lines > 0 {
+            write!(f, ", {} invalid lines skipped", self.invalid_lines)?;
+        }
+        if !self.format_drift.is_empty() {
+            write!(f, ", {} with unrecognised formats", self.format_drift.len())?;
+        }
+        Ok(())
+    }
+}
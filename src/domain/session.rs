@@ -0,0 +1,55 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::AgentRecord;
+
+/// A session as listed for "what did I work on", without per-day or
+/// per-event detail.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub agent: String,
+    pub session_id: Option<String>,
+    pub parent_session_id: Option<String>,
+    pub title: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub model: Option<String>,
+    pub cwd: Option<String>,
+    pub repo_root: Option<String>,
+    pub git_branch: Option<String>,
+    pub tokens: u64,
+    pub tool_calls: usize,
+    /// The session file is gone; the session is known from the cache.
+    pub source_missing: bool,
+}
+
+impl From<&AgentRecord> for SessionSummary {
+    fn from(record: &AgentRecord) -> Self {
+        Self {
+            agent: record.agent_type.to_string(),
+            session_id: record.session_id.clone(),
+            parent_session_id: record.parent_session_id.clone(),
+            title: record.title.clone(),
+            started_at: record.started_at,
+            ended_at: record.ended_at,
+            model: record.model.clone(),
+            cwd: record.cwd.clone(),
+            repo_root: record.repo_root.clone(),
+            git_branch: record.git_branch.clone(),
+            tokens: record.tokens.as_ref().map_or(0, |t| t.total),
+            tool_calls: record.tool_calls.len(),
+            source_missing: record.source_missing,
+        }
+    }
+}
+
+impl AgentRecord {
+    /// Whether the session has activity on `date`.
+    pub fn active_on(&self, date: NaiveDate) -> bool {
+        if self.days.is_empty() {
+            self.date() == date
+        } else {
+            self.days.iter().any(|d| d.date == date)
+        }
+    }
+}
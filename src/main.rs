@@ -7,28 +7,77 @@ use axum::{
     response::Html,
 };
 use tower_http::services::ServeDir;
-use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::sync::broadcast;
 use tower_http::cors::{CorsLayer, Any};
+use clap::{Parser, Subcommand};
+
+#[derive(Parser)]
+#[command(version, about = "Local dashboard for coding agent usage")]
+struct Cli {
+    /// Scan every agent, print what happened to each file and exit
+    #[arg(long)]
+    scan_report: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage the parse cache
+    #[cfg(feature = "cache")]
+    #[command(subcommand)]
+    Cache(CacheCommand),
+}
+
+#[cfg(feature = "cache")]
+#[derive(Subcommand)]
+enum CacheCommand {
+    /// Drop cached records and re-parse every file, for one agent or all
+    Rebuild {
+        /// Agent id, e.g. `claude`; every agent when omitted
+        #[arg(long)]
+        agent: Option<String>,
+    },
+}
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
     tracing_subscriber::fmt::init();
 
+    #[cfg(feature = "cache")]
+    if let Some(Command::Cache(CacheCommand::Rebuild { agent })) = cli.command {
+        let service = services::CollectionService::new()
+            .expect("Failed to initialize collection service");
+        match service.rebuild_cache(agent.as_deref()).await {
+            Ok(scans) => scans.iter().for_each(print_scan),
+            Err(e) => {
+                eprintln!("Cache rebuild failed: {:#}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if cli.scan_report {
+        let service = services::CollectionService::new()
+            .expect("Failed to initialize collection service");
+        service.scan_all().await.iter().for_each(print_scan);
+        return;
+    }
+
     tracing::info!("Starting Agent Insights API server...");
 
     let service = services::CollectionService::new()
         .expect("Failed to initialize collection service");
 
-    match service.collect_all().await {
-        Ok(records) => {
-            tracing::info!("Startup scan complete: {} total records found", records.len());
-        }
-        Err(e) => {
-            tracing::warn!("Startup scan warning: {}", e);
-        }
+    let scans = service.scan_all().await;
+    for scan in &scans {
+        tracing::info!("{}", scan.report);
     }
+    let total: usize = scans.iter().map(|scan| scan.report.records).sum();
+    tracing::info!("Startup scan complete: {} total records found", total);
 
     #[cfg(feature = "cache")]
     if let Ok(stats) = service.cache_stats() {
@@ -42,19 +91,10 @@ async fn main() {
     let (tx, _rx) = broadcast::channel::<websocket::UpdateMessage>(100);
     let ws_state = Arc::new(websocket::WsState { tx: tx.clone() });
 
-    // Start file watcher
-    let home_dir = std::env::var("HOME")
-        .or_else(|_| std::env::var("USERPROFILE"))
-        .unwrap_or_else(|_| ".".to_string());
-
-    let watch_paths = vec![
-        ("Claude".to_string(), PathBuf::from(&home_dir).join(".claude").join("projects")),
-        ("Gemini".to_string(), PathBuf::from(&home_dir).join(".gemini").join("tmp")),
-        ("Codex".to_string(), PathBuf::from(&home_dir).join(".codex").join("sessions")),
-    ];
-
-    let mut watcher = websocket::FileWatcher::new(tx);
-    if let Err(e) = watcher.start(watch_paths) {
+    // Start file watcher over the same scanners used for collection
+    let registry = Arc::new(service.registry().clone());
+    let mut watcher = websocket::FileWatcher::new(tx, registry);
+    if let Err(e) = watcher.start() {
         tracing::warn!("Failed to start file watcher: {}", e);
     }
 
@@ -81,6 +121,8 @@ async fn main() {
         )
         .route("/api/heatmaps", get(api::get_all_heatmaps))
         .route("/api/heatmap/:agent", get(api::get_agent_heatmap))
+        .route("/api/scan/report", get(api::get_scan_report))
+        .route("/api/sessions", get(api::get_sessions))
         .route("/ws", get(websocket::ws_handler))
         .layer(cors)
         .with_state(ws_state);
@@ -92,10 +134,23 @@ async fn main() {
     tracing::info!("Server running on http://127.0.0.1:3001");
     tracing::info!("Available endpoints:");
     tracing::info!("  GET /api/heatmaps - Get all agent heatmaps");
-    tracing::info!("  GET /api/heatmap/:agent - Get specific agent heatmap (claude, gemini, codex)");
+    tracing::info!("  GET /api/heatmap/:agent - Get specific agent heatmap (claude, gemini, codex, ...)");
+    tracing::info!("  GET /api/scan/report - Per-agent scan report with parse failures");
+    tracing::info!("  GET /api/sessions?date=YYYY-MM-DD&agent=claude - Sessions with titles");
     tracing::info!("  WS  /ws - WebSocket for real-time updates");
 
     axum::serve(listener, app)
         .await
         .expect("Server failed to start");
 }
+
+/// Print a scan's report with every failed and drifting file.
+fn print_scan(scan: &agent_insights::agents::Scan) {
+    println!("{}", scan.report);
+    for failure in &scan.report.failures {
+        println!("  failed {}: {}", failure.path.display(), failure.reason);
+    }
+    for drift in &scan.report.format_drift {
+        println!("  format {}: {}", drift.path.display(), drift.reason);
+    }
+}
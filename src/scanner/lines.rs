@@ -0,0 +1,297 @@
+use serde_json::Value;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Lines of a text file, read one at a time so that large session logs are
+/// never held in memory whole. Invalid UTF-8 is replaced rather than failing
+/// the file.
+///
+/// Iteration stops at the first I/O error, which would otherwise look like
+/// the end of the file; [`finish`] returns it.
+///
+/// [`finish`]: LossyLines::finish
+pub struct LossyLines<R> {
+    reader: R,
+    buf: Vec<u8>,
+    error: Option<io::Error>,
+    /// Byte offset just past the last newline read.
+    complete_offset: u64,
+    /// Byte offset just past the last line read, terminated or not.
+    offset: u64,
+}
+
+impl LossyLines<BufReader<File>> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::open_at(path, 0)
+    }
+
+    /// Start reading at byte `offset`, which should be the start of a line.
+    pub fn open_at(path: &Path, offset: u64) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut lines = Self::new(BufReader::new(file));
+        lines.offset = offset;
+        lines.complete_offset = offset;
+        Ok(lines)
+    }
+}
+
+impl<R: BufRead> LossyLines<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, buf: Vec::new(), error: None, complete_offset: 0, offset: 0 }
+    }
+
+    /// The I/O error that stopped reading before the end of the file, if any.
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.error.take().map_or(Ok(()), Err)
+    }
+
+    /// Offset just past the last newline-terminated line read. A final line
+    /// without a newline may still be being written.
+    pub fn complete_offset(&self) -> u64 {
+        self.complete_offset
+    }
+
+    /// Whether the last line read ended with a newline.
+    pub fn last_terminated(&self) -> bool {
+        self.complete_offset == self.offset
+    }
+}
+
+impl<R: BufRead> Iterator for LossyLines<R> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        self.buf.clear();
+        match self.reader.read_until(b'\n', &mut self.buf) {
+            Ok(0) => None,
+            Ok(n) => {
+                self.offset += n as u64;
+                if self.buf.ends_with(b"\n") {
+                    self.complete_offset = self.offset;
+                }
+                let line = self.buf.strip_suffix(b"\n").unwrap_or(&self.buf);
+                let line = line.strip_suffix(b"\r").unwrap_or(line);
+                Some(String::from_utf8_lossy(line).into_owned())
+            }
+            Err(e) => {
+                self.error = Some(e);
+                None
+            }
+        }
+    }
+}
+
+/// The bytes of a text file with invalid UTF-8 replaced, for streaming a
+/// whole JSON document to `serde_json::from_reader` without one bad byte
+/// failing the file. Decodes a line at a time, so multi-byte characters are
+/// never split.
+pub struct LossyReader<R> {
+    reader: R,
+    line: Vec<u8>,
+    pos: usize,
+}
+
+impl LossyReader<BufReader<File>> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> LossyReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, line: Vec::new(), pos: 0 }
+    }
+}
+
+impl<R: BufRead> Read for LossyReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.line.len() {
+            self.line.clear();
+            self.pos = 0;
+            if self.reader.read_until(b'\n', &mut self.line)? == 0 {
+                return Ok(0);
+            }
+            let replaced = match String::from_utf8_lossy(&self.line) {
+                Cow::Owned(replaced) => Some(replaced),
+                Cow::Borrowed(_) => None,
+            };
+            if let Some(replaced) = replaced {
+                self.line = replaced.into_bytes();
+            }
+        }
+        let n = (&self.line[self.pos..]).read(out)?;
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// JSON values of a JSONL file, one per non-empty line.
+///
+/// Lines that are not valid JSON are skipped and counted. Reading is lazy:
+/// a caller that only needs the first few lines can stop iterating.
+pub struct JsonlReader<R> {
+    lines: LossyLines<R>,
+    invalid: usize,
+}
+
+impl JsonlReader<BufReader<File>> {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::open_at(path, 0)
+    }
+
+    /// Start reading at byte `offset`, which should be the start of a line.
+    pub fn open_at(path: &Path, offset: u64) -> io::Result<Self> {
+        Ok(Self { lines: LossyLines::open_at(path, offset)?, invalid: 0 })
+    }
+}
+
+impl<R: BufRead> JsonlReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { lines: LossyLines::new(reader), invalid: 0 }
+    }
+
+    /// Non-empty lines skipped so far because they did not parse. A last
+    /// line without a newline may still be being written and is not counted.
+    pub fn invalid_lines(&self) -> usize {
+        self.invalid
+    }
+
+    /// See [`LossyLines::finish`].
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.lines.finish()
+    }
+
+    /// See [`LossyLines::complete_offset`].
+    pub fn complete_offset(&self) -> u64 {
+        self.lines.complete_offset()
+    }
+
+    /// Whether the value last returned came from a newline-terminated line.
+    pub fn last_terminated(&self) -> bool {
+        self.lines.last_terminated()
+    }
+}
+
+/// Result of [`read_jsonl_from`].
+pub struct JsonlTail {
+    /// Offset to resume from next time: just past the last complete line.
+    pub offset: u64,
+    /// A final line without a trailing newline. It parsed, but may be
+    /// rewritten, so it is not part of the resumable prefix.
+    pub tail: Option<Value>,
+    /// Complete lines skipped because they were not valid JSON.
+    pub invalid_lines: usize,
+}
+
+/// Feed each complete JSONL line of `path` from byte `offset` onwards to
+/// `feed`, so that parsing can later resume where it stopped.
+pub fn read_jsonl_from(path: &Path, offset: u64, mut feed: impl FnMut(&Value)) -> io::Result<JsonlTail> {
+    let mut reader = JsonlReader::open_at(path, offset)?;
+    let mut tail = None;
+    while let Some(value) = reader.next() {
+        if reader.last_terminated() {
+            feed(&value);
+        } else {
+            tail = Some(value);
+        }
+    }
+    reader.finish()?;
+    if reader.invalid_lines() > 0 {
+        tracing::debug!("Skipped {} invalid lines in {:?}", reader.invalid_lines(), path);
+    }
+    Ok(JsonlTail { offset: reader.complete_offset(), tail, invalid_lines: reader.invalid_lines() })
+}
+
+/// Number of leading bytes hashed to detect a file rewritten in place.
+pub const PREFIX_LEN: u64 = 4096;
+
+/// FNV-1a hash of the first `min(len, PREFIX_LEN)` bytes of `path`.
+pub fn prefix_hash(path: &Path, len: u64) -> io::Result<u64> {
+    let mut prefix = Vec::new();
+    File::open(path)?
+        .take(len.min(PREFIX_LEN))
+        .read_to_end(&mut prefix)?;
+    Ok(prefix.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    }))
+}
+
+impl<R: BufRead> Iterator for JsonlReader<R> {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Value> {
+        while let Some(line) = self.lines.next() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            match serde_json::from_str(line) {
+                Ok(value) => return Some(value),
+                Err(_) if self.lines.last_terminated() => self.invalid += 1,
+                Err(_) => {}
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_and_counts_invalid_lines() {
+        let mut data = b"{\"a\":1}\r\n\n{\"b\":\"".to_vec();
+        data.extend_from_slice(&[0xff, 0xfe]);
+        data.extend_from_slice(b"\"}\nnot json\n{\"c\":3}");
+
+        let mut reader = JsonlReader::new(&data[..]);
+        let values: Vec<Value> = reader.by_ref().collect();
+
+        assert_eq!(values.len(), 3);
+        assert_eq!(values[1]["b"], "\u{fffd}\u{fffd}");
+        assert_eq!(values[2]["c"], 3);
+        assert_eq!(reader.invalid_lines(), 1);
+    }
+
+    #[test]
+    fn unterminated_invalid_line_is_not_counted() {
+        let mut reader = JsonlReader::new(&b"{\"a\":1}\nnot json\n{\"b\":"[..]);
+        assert_eq!(reader.by_ref().count(), 1);
+        assert_eq!(reader.invalid_lines(), 1);
+    }
+
+    #[test]
+    fn lossy_reader_replaces_invalid_bytes() {
+        let mut data = b"{\"a\": \"caf\xc3\xa9\",\n \"b\": \"".to_vec();
+        data.extend_from_slice(&[0xff, 0xfe]);
+        data.extend_from_slice(b"\"}\n");
+
+        let value: Value = serde_json::from_reader(LossyReader::new(&data[..])).unwrap();
+
+        assert_eq!(value["a"], "caf\u{e9}");
+        assert_eq!(value["b"], "\u{fffd}\u{fffd}");
+    }
+
+    /// Fails every read, like a file on a disconnected network share.
+    struct Failing;
+
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("device went away"))
+        }
+    }
+
+    #[test]
+    fn io_errors_end_reading_and_are_returned() {
+        let mut lines = LossyLines::new(BufReader::new((&b"first\nsecond"[..]).chain(Failing)));
+
+        assert_eq!(lines.next().as_deref(), Some("first"));
+        assert_eq!(lines.next(), None);
+        assert_eq!(lines.finish().unwrap_err().to_string(), "device went away");
+        assert!(lines.finish().is_ok());
+    }
+}
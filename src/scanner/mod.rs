@@ -1,9 +1,22 @@
+use crate::domain::ScanFailure;
 use anyhow::Result;
 use chrono::{DateTime, Utc};
 use std::fs;
 use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
+pub mod git;
+pub mod lines;
+pub mod parallel;
+pub mod timestamps;
+pub mod title;
+
+pub use git::{fill_from_checkout, find_checkout, redact_remote, GitCheckout};
+pub use lines::{prefix_hash, read_jsonl_from, JsonlReader, JsonlTail, LossyLines, LossyReader};
+pub use parallel::{default_workers, parallel_map};
+pub use timestamps::{from_unix, parse_timestamp, ActivityRange, DailyActivity};
+pub use title::session_title;
+
 #[derive(Debug, Clone)]
 pub struct FileInfo {
     pub path: PathBuf,
@@ -12,13 +25,41 @@ pub struct FileInfo {
     pub size: u64,
 }
 
+impl FileInfo {
+    /// Count `other` being written to as a change to this file, for
+    /// sessions whose data is spread over several files.
+    pub fn touched_with(&mut self, other: &Path) {
+        if let Ok(modified) = fs::metadata(other).and_then(|m| m.modified()) {
+            self.modified_at = self.modified_at.max(modified.into());
+        }
+    }
+}
+
 pub struct FileScanner {
     pub root: PathBuf,
+    /// Maximum directory depth below `root`; unlimited when `None`.
+    pub max_depth: Option<usize>,
+    /// Directory names that are never descended into.
+    pub ignored_dirs: Vec<String>,
 }
 
 impl FileScanner {
     pub fn new(root: PathBuf) -> Self {
-        Self { root }
+        Self {
+            root,
+            max_depth: None,
+            ignored_dirs: Vec::new(),
+        }
+    }
+
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    pub fn with_ignored_dirs(mut self, dirs: &[&str]) -> Self {
+        self.ignored_dirs = dirs.iter().map(|d| d.to_string()).collect();
+        self
     }
 
     pub fn scan_json_files(&self) -> Result<Vec<FileInfo>> {
@@ -30,33 +71,81 @@ impl FileScanner {
     }
 
     pub fn scan_files_with_extension(&self, ext: &str) -> Result<Vec<FileInfo>> {
+        self.scan_matching(|path| path.extension().is_some_and(|e| e == ext))
+    }
+
+    /// Walk the root and return every regular file accepted by `filter`.
+    /// Entries that cannot be read are logged and left out.
+    pub fn scan_matching<F>(&self, filter: F) -> Result<Vec<FileInfo>>
+    where
+        F: Fn(&Path) -> bool,
+    {
+        let mut failures = Vec::new();
+        let files = self.walk_matching(filter, &mut failures);
+        for failure in failures {
+            tracing::warn!("Skipped {:?}: {}", failure.path, failure.reason);
+        }
+        Ok(files)
+    }
+
+    /// Like [`scan_matching`], recording entries that cannot be read in
+    /// `failures` instead of logging them.
+    ///
+    /// [`scan_matching`]: FileScanner::scan_matching
+    pub fn walk_matching<F>(&self, filter: F, failures: &mut Vec<ScanFailure>) -> Vec<FileInfo>
+    where
+        F: Fn(&Path) -> bool,
+    {
         let mut files = Vec::new();
 
         if !self.root.exists() {
             tracing::warn!("Directory does not exist: {:?}", self.root);
-            return Ok(files);
+            return files;
+        }
+
+        let mut walker = WalkDir::new(&self.root).follow_links(false);
+        if let Some(depth) = self.max_depth {
+            walker = walker.max_depth(depth);
         }
 
-        for entry in WalkDir::new(&self.root)
-            .follow_links(false)
+        for entry in walker
             .into_iter()
-            .filter_map(|e| e.ok())
+            .filter_entry(|e| !self.is_ignored_dir(e))
         {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    failures.push(ScanFailure {
+                        path: e.path().unwrap_or(&self.root).to_path_buf(),
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+            };
             let path = entry.path();
-            if !path.is_file() {
+            if !path.is_file() || !filter(path) {
                 continue;
             }
 
-            if let Some(file_ext) = path.extension() {
-                if file_ext == ext {
-                    if let Ok(file_info) = self.get_file_info(path) {
-                        files.push(file_info);
-                    }
-                }
+            match self.get_file_info(path) {
+                Ok(file_info) => files.push(file_info),
+                Err(e) => failures.push(ScanFailure {
+                    path: path.to_path_buf(),
+                    reason: e.to_string(),
+                }),
             }
         }
 
-        Ok(files)
+        files
+    }
+
+    fn is_ignored_dir(&self, entry: &walkdir::DirEntry) -> bool {
+        entry.depth() > 0
+            && entry.file_type().is_dir()
+            && entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| self.ignored_dirs.iter().any(|d| d == name))
     }
 
     fn get_file_info(&self, path: &Path) -> Result<FileInfo> {
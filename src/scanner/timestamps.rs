@@ -0,0 +1,97 @@
+use crate::domain::DayUsage;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Parse a timestamp as agents write them: an RFC 3339 string or a Unix
+/// epoch in seconds or milliseconds.
+pub fn parse_timestamp(value: &Value) -> Option<DateTime<Utc>> {
+    match value {
+        Value::String(s) => DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|dt| dt.with_timezone(&Utc)),
+        Value::Number(n) => from_unix(n.as_i64()?),
+        _ => None,
+    }
+}
+
+/// Unix seconds, or milliseconds for values too large to be seconds.
+pub fn from_unix(value: i64) -> Option<DateTime<Utc>> {
+    if value > 10_000_000_000 {
+        DateTime::from_timestamp_millis(value)
+    } else {
+        DateTime::from_timestamp(value, 0)
+    }
+}
+
+/// Earliest and latest event timestamps seen in a session.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct ActivityRange {
+    pub first: Option<DateTime<Utc>>,
+    pub last: Option<DateTime<Utc>>,
+}
+
+impl ActivityRange {
+    pub fn observe(&mut self, at: DateTime<Utc>) {
+        self.first = Some(self.first.map_or(at, |first| first.min(at)));
+        self.last = Some(self.last.map_or(at, |last| last.max(at)));
+    }
+
+    pub fn observe_value(&mut self, value: Option<&Value>) {
+        if let Some(at) = value.and_then(parse_timestamp) {
+            self.observe(at);
+        }
+    }
+
+    /// `(started_at, ended_at)`, falling back to the given file times.
+    pub fn or_file_times(&self, created_at: DateTime<Utc>, modified_at: DateTime<Utc>) -> (DateTime<Utc>, DateTime<Utc>) {
+        (self.first.unwrap_or(created_at), self.last.unwrap_or(modified_at))
+    }
+}
+
+/// Messages, tokens and tool calls bucketed by the UTC day they happened.
+///
+/// Events are filed under the most recent timestamp seen; anything before
+/// the first timestamp is counted on the session's first day.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct DailyActivity {
+    days: BTreeMap<NaiveDate, DayUsage>,
+    undated: DayUsage,
+    current: Option<NaiveDate>,
+}
+
+impl DailyActivity {
+    /// Move the clock forward to the event at `value`, if it is a timestamp.
+    pub fn observe_value(&mut self, value: Option<&Value>) {
+        if let Some(at) = value.and_then(parse_timestamp) {
+            self.current = Some(at.date_naive());
+        }
+    }
+
+    /// Day of the most recent timestamp seen.
+    pub fn current(&self) -> Option<NaiveDate> {
+        self.current
+    }
+
+    /// Usage bucket for the current day.
+    pub fn today(&mut self) -> &mut DayUsage {
+        self.day(self.current)
+    }
+
+    /// Usage bucket for `date`, or for the first day when unknown.
+    pub fn day(&mut self, date: Option<NaiveDate>) -> &mut DayUsage {
+        match date {
+            Some(date) => self.days.entry(date).or_insert_with(|| DayUsage::new(date)),
+            None => &mut self.undated,
+        }
+    }
+
+    /// Days with activity, in order. Empty when the log carried no timestamps.
+    pub fn into_days(mut self) -> Vec<DayUsage> {
+        if let Some(first) = self.days.values_mut().next() {
+            first.add(&self.undated);
+        }
+        self.days.into_values().collect()
+    }
+}
@@ -1,33 +1,100 @@
-use crate::domain::{AgentRecord, AgentType, HeatmapData, ToolCallStats, TokenStats};
+use crate::domain::{
+    tool_invocations, version_parts, AgentRecord, DayActivity, DayUsage, HeatmapData, ToolCallStats,
+    ToolOutcome, TokenStats, VersionStats,
+};
 use chrono::NaiveDate;
+use std::cmp::Reverse;
 use std::collections::HashMap;
 
 pub struct AggregationService;
 
+/// Options for [`AggregationService::aggregate_by_agent_with`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AggregateOptions {
+    /// Report sub-agent sessions as their own "<Agent> sub-agents" heatmap
+    /// instead of rolling their usage into the parent agent.
+    pub split_subagents: bool,
+}
+
 impl AggregationService {
+    /// Aggregate one agent's records. Sub-agent sessions contribute their
+    /// tokens, tool calls and size but are not counted as sessions, since
+    /// their parent already is.
     pub fn aggregate_by_date(records: Vec<AgentRecord>) -> HeatmapData {
-        let mut day_counts: HashMap<NaiveDate, (usize, u64)> = HashMap::new();
+        let agent = if !records.is_empty() {
+            records[0].agent_type.to_string()
+        } else {
+            "Unknown".to_string()
+        };
+        Self::aggregate(agent, records, false)
+    }
+
+    fn aggregate(agent: String, records: Vec<AgentRecord>, count_subagents: bool) -> HeatmapData {
+        let mut days: HashMap<NaiveDate, DayActivity> = HashMap::new();
+        let mut total_files = 0usize;
         let mut tool_call_counts: HashMap<String, usize> = HashMap::new();
+        let mut tool_outcomes: HashMap<String, ToolOutcomes> = HashMap::new();
         let mut model_counts: HashMap<String, usize> = HashMap::new();
+        let mut versions: HashMap<String, VersionStats> = HashMap::new();
         let mut total_input = 0u64;
         let mut total_output = 0u64;
         let mut total_cache_creation = 0u64;
         let mut total_cache_read = 0u64;
         let mut total_tokens = 0u64;
-
-        let agent_type = records.first().map(|r| &r.agent_type);
-        let use_api_total = matches!(agent_type, Some(AgentType::Codex) | Some(AgentType::Gemini));
+        let mut total_reasoning = 0u64;
 
         for record in &records {
-            let date = record.date();
-            let entry = day_counts.entry(date).or_insert((0, 0));
-            entry.0 += 1;
-            entry.1 += record.file_size;
+            let counted = count_subagents || !record.is_subagent();
+            if counted {
+                total_files += 1;
+            }
+
+            days.entry(record.date())
+                .or_insert_with(|| DayActivity::new(record.date()))
+                .size += record.file_size;
+
+            for usage in Self::record_days(record) {
+                let day = days.entry(usage.date).or_insert_with(|| DayActivity::new(usage.date));
+                if counted {
+                    day.sessions += 1;
+                }
+                day.messages += usage.messages;
+                day.tokens += usage.tokens;
+                day.tool_calls += usage.tool_calls;
+            }
 
             for tool in &record.tool_calls {
                 *tool_call_counts.entry(tool.clone()).or_insert(0) += 1;
             }
 
+            let version = record.cli_version.clone().unwrap_or_else(|| "unknown".to_string());
+            let version_stats = versions.entry(version.clone()).or_insert_with(|| VersionStats {
+                version,
+                sessions: 0,
+                tokens: 0,
+                tool_calls: 0,
+                tool_errors: 0,
+                tool_error_rate: 0.0,
+            });
+            if counted {
+                version_stats.sessions += 1;
+            }
+            version_stats.tokens += Self::record_tokens(record);
+            version_stats.tool_calls += record.tool_calls.len();
+
+            for invocation in tool_invocations(&record.events) {
+                let outcomes = tool_outcomes.entry(invocation.tool_name).or_default();
+                match invocation.outcome {
+                    Some(ToolOutcome::Error) => {
+                        outcomes.errors += 1;
+                        version_stats.tool_errors += 1;
+                    }
+                    Some(ToolOutcome::Rejected) => outcomes.rejected += 1,
+                    _ => {}
+                }
+                outcomes.durations.extend(invocation.duration_ms);
+            }
+
             if let Some(ref m) = record.model {
                 *model_counts.entry(m.clone()).or_insert(0) += 1;
             }
@@ -37,41 +104,40 @@ impl AggregationService {
                 total_output += tokens.output;
                 total_cache_creation += tokens.cache_creation;
                 total_cache_read += tokens.cached;
-                if use_api_total {
-                    total_tokens += tokens.total;
-                }
+                total_reasoning += tokens.reasoning;
+                total_tokens += tokens.total;
             }
         }
 
-        let agent = if !records.is_empty() {
-            format!("{:?}", records[0].agent_type)
-        } else {
-            "Unknown".to_string()
-        };
-
         let mut tool_calls: Vec<ToolCallStats> = tool_call_counts
             .into_iter()
-            .map(|(tool_name, count)| ToolCallStats { tool_name, count })
+            .map(|(tool_name, count)| {
+                let mut outcomes = tool_outcomes.remove(&tool_name).unwrap_or_default();
+                outcomes.durations.sort_unstable();
+                ToolCallStats {
+                    count,
+                    error_count: outcomes.errors,
+                    rejected_count: outcomes.rejected,
+                    p50_duration_ms: percentile(&outcomes.durations, 50),
+                    p95_duration_ms: percentile(&outcomes.durations, 95),
+                    tool_name,
+                }
+            })
             .collect();
-        tool_calls.sort_by(|a, b| b.count.cmp(&a.count));
+        tool_calls.sort_by_key(|t| Reverse(t.count));
 
         let mut models: Vec<(String, usize)> = model_counts.into_iter().collect();
-        models.sort_by(|a, b| b.1.cmp(&a.1));
+        models.sort_by_key(|(_, count)| Reverse(*count));
         let models: Vec<String> = models.into_iter().map(|(m, _)| m).collect();
 
-        let final_total = if use_api_total {
-            total_tokens
-        } else {
-            total_input + total_output + total_cache_creation + total_cache_read
-        };
-
-        let total_reasoning: u64 = if use_api_total {
-            records.iter()
-                .filter_map(|r| r.tokens.as_ref().map(|t| t.reasoning))
-                .sum()
-        } else {
-            0
-        };
+        let mut versions: Vec<VersionStats> = versions.into_values().collect();
+        for v in &mut versions {
+            if v.tool_calls > 0 {
+                v.tool_error_rate = v.tool_errors as f64 / v.tool_calls as f64;
+            }
+        }
+        // Newest first; "unknown" has no numeric parts and sorts last
+        versions.sort_by(|a, b| version_parts(&b.version).cmp(&version_parts(&a.version)).then_with(|| a.version.cmp(&b.version)));
 
         let token_stats = TokenStats {
             input_tokens: total_input,
@@ -79,26 +145,248 @@ impl AggregationService {
             cache_creation_tokens: total_cache_creation,
             cache_read_tokens: total_cache_read,
             reasoning_tokens: if total_reasoning > 0 { Some(total_reasoning) } else { None },
-            total_tokens: final_total,
+            total_tokens,
         };
 
-        HeatmapData::from_counts(agent, day_counts, tool_calls, token_stats, models)
+        HeatmapData::from_days(agent, days, total_files, tool_calls, token_stats, models, versions)
+    }
+
+    /// A record's per-day usage, or all of it on its start day when the
+    /// parser could not split it.
+    fn record_days(record: &AgentRecord) -> Vec<DayUsage> {
+        if !record.days.is_empty() {
+            return record.days.clone();
+        }
+        vec![DayUsage {
+            date: record.date(),
+            messages: 0,
+            tokens: Self::record_tokens(record),
+            tool_calls: record.tool_calls.len(),
+        }]
+    }
+
+    /// A record's token total, as its parser counted it.
+    fn record_tokens(record: &AgentRecord) -> u64 {
+        record.tokens.as_ref().map_or(0, |t| t.total)
     }
 
     pub fn aggregate_by_agent(records: Vec<AgentRecord>) -> HashMap<String, HeatmapData> {
+        Self::aggregate_by_agent_with(records, AggregateOptions::default())
+    }
+
+    pub fn aggregate_by_agent_with(records: Vec<AgentRecord>, options: AggregateOptions) -> HashMap<String, HeatmapData> {
         let mut by_agent: HashMap<String, Vec<AgentRecord>> = HashMap::new();
 
         for record in records {
-            let agent_key = format!("{:?}", record.agent_type);
+            let agent_key = if options.split_subagents && record.is_subagent() {
+                format!("{} sub-agents", record.agent_type)
+            } else {
+                record.agent_type.to_string()
+            };
             by_agent.entry(agent_key).or_default().push(record);
         }
 
         by_agent
             .into_iter()
             .map(|(agent, records)| {
-                let heatmap = Self::aggregate_by_date(records);
+                let heatmap = Self::aggregate(agent.clone(), records, options.split_subagents);
                 (agent, heatmap)
             })
             .collect()
     }
 }
+
+/// Outcomes gathered for one tool across sessions.
+#[derive(Debug, Default)]
+struct ToolOutcomes {
+    errors: usize,
+    rejected: usize,
+    durations: Vec<u64>,
+}
+
+/// Nearest-rank percentile of already sorted values.
+fn percentile(sorted: &[u64], p: usize) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted.get(rank - 1).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::{AgentEvent, AgentType, EventKind, TokenInfo};
+    use chrono::{DateTime, Duration, Utc};
+
+    fn record(agent_type: AgentType, started_at: &str) -> AgentRecord {
+        let started_at: DateTime<Utc> = started_at.parse().unwrap();
+        AgentRecord {
+            agent_type,
+            file_path: format!("/logs/{}.jsonl", started_at.timestamp()),
+            created_at: started_at,
+            modified_at: started_at,
+            started_at,
+            ended_at: started_at,
+            file_size: 10,
+            session_id: None,
+            parent_session_id: None,
+            model: None,
+            cwd: None,
+            title: None,
+            tokens: None,
+            tool_calls: Vec::new(),
+            days: Vec::new(),
+            events: Vec::new(),
+            format: None,
+            cli_version: None,
+            git_branch: None,
+            git_commit: None,
+            repo_root: None,
+            repo_remote: None,
+            source_missing: false,
+        }
+    }
+
+    fn day(date: &str, messages: usize, tokens: u64, tool_calls: usize) -> DayUsage {
+        DayUsage { date: date.parse().unwrap(), messages, tokens, tool_calls }
+    }
+
+    fn tokens(input: u64, output: u64, cached: u64, total: u64) -> Option<TokenInfo> {
+        Some(TokenInfo { input, output, cached, cache_creation: 0, reasoning: 0, total })
+    }
+
+    /// Date, sessions, messages, tokens, tool calls and size of each day.
+    fn days(heatmap: &HeatmapData) -> Vec<(&str, usize, usize, u64, usize, u64)> {
+        heatmap.data.iter()
+            .map(|d| (d.date.as_str(), d.sessions, d.messages, d.tokens, d.tool_calls, d.size))
+            .collect()
+    }
+
+    #[test]
+    fn sessions_spanning_midnight_count_on_each_day() {
+        let mut late = record(AgentType::Codex, "2025-03-01T23:30:00Z");
+        late.days = vec![day("2025-03-01", 2, 100, 1), day("2025-03-02", 3, 200, 2)];
+        let mut next = record(AgentType::Codex, "2025-03-02T09:00:00Z");
+        next.file_size = 5;
+        next.days = vec![day("2025-03-02", 1, 50, 0)];
+
+        let heatmap = AggregationService::aggregate_by_date(vec![late, next]);
+
+        // Size counts on the day a session started, activity on each day it touched
+        assert_eq!(days(&heatmap), vec![
+            ("2025-03-01", 1, 2, 100, 1, 10),
+            ("2025-03-02", 2, 4, 250, 2, 5),
+        ]);
+        assert_eq!(heatmap.max_count, 2);
+        assert_eq!(heatmap.total_files, 2);
+    }
+
+    #[test]
+    fn records_without_days_count_on_their_start_date() {
+        let mut codex = record(AgentType::Codex, "2025-03-01T23:30:00Z");
+        codex.tokens = tokens(100, 20, 30, 500);
+        codex.tool_calls = vec!["shell:ls".to_string(), "apply_patch".to_string()];
+
+        let heatmap = AggregationService::aggregate_by_date(vec![codex]);
+
+        assert_eq!(days(&heatmap), vec![("2025-03-01", 1, 0, 500, 2, 10)]);
+    }
+
+    #[test]
+    fn totals_are_the_records_own() {
+        // However each agent counts its total, the heatmap adds up the same
+        // numbers as the records rather than recomputing them from the parts
+        for agent_type in [AgentType::Claude, AgentType::Codex, AgentType::OpenCode, AgentType::Aider] {
+            let mut session = record(agent_type.clone(), "2025-03-01T10:00:00Z");
+            session.tokens = Some(TokenInfo { input: 100, output: 20, cached: 30, cache_creation: 4, reasoning: 5, total: 159 });
+
+            let heatmap = AggregationService::aggregate_by_date(vec![session]);
+
+            assert_eq!(heatmap.token_stats.total_tokens, 159, "{}", agent_type);
+            assert_eq!(heatmap.token_stats.reasoning_tokens, Some(5), "{}", agent_type);
+            assert_eq!(heatmap.data[0].tokens, 159, "{}", agent_type);
+        }
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        assert_eq!(percentile(&[], 50), None);
+        assert_eq!(percentile(&[7], 50), Some(7));
+        assert_eq!(percentile(&[7], 95), Some(7));
+        assert_eq!(percentile(&[10, 20, 30, 40], 0), Some(10));
+        assert_eq!(percentile(&[10, 20, 30, 40], 50), Some(20));
+        assert_eq!(percentile(&[10, 20, 30, 40], 95), Some(40));
+    }
+
+    #[test]
+    fn tool_outcomes_are_counted_per_tool() {
+        let start: DateTime<Utc> = "2025-03-01T10:00:00Z".parse().unwrap();
+        let at = |secs: i64| Some(start + Duration::seconds(secs));
+        let call = |name: &str, id: &str, secs: i64| {
+            AgentEvent::new(EventKind::ToolCall, at(secs)).with_tool(Some(name), Some(id))
+        };
+        let result = |id: &str, secs: i64, outcome: ToolOutcome| {
+            AgentEvent::new(EventKind::ToolResult, at(secs)).with_tool(None, Some(id)).with_outcome(Some(outcome))
+        };
+        let mut session = record(AgentType::Claude, "2025-03-01T10:00:00Z");
+        session.tool_calls = ["Bash", "Bash", "Bash", "Read"].map(String::from).to_vec();
+        session.events = vec![
+            call("Bash", "c1", 0),
+            result("c1", 1, ToolOutcome::Success),
+            call("Bash", "c2", 10),
+            result("c2", 13, ToolOutcome::Error),
+            call("Bash", "c3", 20),
+            result("c3", 22, ToolOutcome::Rejected),
+            // Interrupted before its result was logged
+            call("Read", "c4", 30),
+        ];
+
+        let heatmap = AggregationService::aggregate_by_date(vec![session]);
+
+        let stats: Vec<_> = heatmap.tool_calls.iter()
+            .map(|t| (t.tool_name.as_str(), t.count, t.error_count, t.rejected_count, t.p50_duration_ms, t.p95_duration_ms))
+            .collect();
+        assert_eq!(stats, vec![
+            ("Bash", 3, 1, 1, Some(2_000), Some(3_000)),
+            ("Read", 1, 0, 0, None, None),
+        ]);
+    }
+
+    #[test]
+    fn versions_are_broken_down_newest_first() {
+        let start: DateTime<Utc> = "2025-03-01T10:00:00Z".parse().unwrap();
+        let session = |version: Option<&str>, outcomes: &[ToolOutcome]| {
+            let mut session = record(AgentType::Codex, "2025-03-01T10:00:00Z");
+            session.cli_version = version.map(String::from);
+            session.tokens = tokens(10, 5, 0, 100);
+            for (i, outcome) in outcomes.iter().enumerate() {
+                let id = format!("c{}", i);
+                session.tool_calls.push("shell:ls".to_string());
+                session.events.push(AgentEvent::new(EventKind::ToolCall, Some(start)).with_tool(Some("shell:ls"), Some(&id)));
+                session.events.push(AgentEvent::new(EventKind::ToolResult, Some(start)).with_tool(None, Some(&id)).with_outcome(Some(*outcome)));
+            }
+            session
+        };
+        let records = vec![
+            session(Some("0.9.0"), &[ToolOutcome::Error]),
+            session(None, &[]),
+            session(Some("0.46.0"), &[ToolOutcome::Success, ToolOutcome::Error]),
+            session(Some("0.46.0"), &[ToolOutcome::Success, ToolOutcome::Rejected]),
+            session(Some("v0.47.0"), &[]),
+        ];
+
+        let heatmap = AggregationService::aggregate_by_date(records);
+
+        let versions: Vec<_> = heatmap.versions.iter()
+            .map(|v| (v.version.as_str(), v.sessions, v.tokens, v.tool_calls, v.tool_errors, v.tool_error_rate))
+            .collect();
+        // Numeric rather than string order; rejected calls are not errors
+        assert_eq!(versions, vec![
+            ("v0.47.0", 1, 100, 0, 0, 0.0),
+            ("0.46.0", 2, 200, 4, 1, 0.25),
+            ("0.9.0", 1, 100, 1, 1, 1.0),
+            ("unknown", 1, 100, 0, 0, 0.0),
+        ]);
+    }
+}
@@ -1,15 +1,26 @@
 #[cfg(feature = "cache")]
-use crate::cache::CacheDb;
-use crate::agents::{ClaudeScanner, GeminiScanner, CodexScanner};
-use crate::domain::AgentRecord;
+use crate::cache::{keep_content_from_env, CacheDb, MissingFiles};
+use crate::agents::{AgentScanner, Scan, ScanProgress, ScannerRegistry};
+use crate::domain::{AgentRecord, AgentType, ScanReport};
+#[cfg(feature = "cache")]
+use crate::scanner::FileInfo;
 use anyhow::Result;
 #[cfg(feature = "cache")]
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use tokio::task::JoinSet;
+
+/// Receives progress from scans running on the blocking pool.
+pub type ProgressHandler = Arc<dyn Fn(ScanProgress) + Send + Sync>;
 
 pub struct CollectionService {
-    home_dir: String,
+    registry: ScannerRegistry,
+    progress: ProgressHandler,
     #[cfg(feature = "cache")]
     cache: Arc<CacheDb>,
+    #[cfg(feature = "cache")]
+    missing_files: MissingFiles,
 }
 
 impl CollectionService {
@@ -19,72 +30,303 @@ impl CollectionService {
             .unwrap_or_else(|_| ".".to_string());
 
         #[cfg(feature = "cache")]
-        let cache = Arc::new(CacheDb::new(None)?);
+        let cache = Arc::new(CacheDb::new(None)?.with_content(keep_content_from_env()));
+
+        #[cfg(feature = "cache")]
+        let registry = ScannerRegistry::with_cache(&home_dir, Arc::clone(&cache));
+        #[cfg(not(feature = "cache"))]
+        let registry = ScannerRegistry::with_defaults(&home_dir);
 
         Ok(Self {
-            home_dir,
+            registry,
+            progress: Arc::new(log_progress),
             #[cfg(feature = "cache")]
             cache,
+            #[cfg(feature = "cache")]
+            missing_files: MissingFiles::from_env(),
         })
     }
 
+    /// The scanners this service collects from. Register additional
+    /// scanners here to include them in collection.
+    pub fn registry(&self) -> &ScannerRegistry {
+        &self.registry
+    }
+
+    pub fn registry_mut(&mut self) -> &mut ScannerRegistry {
+        &mut self.registry
+    }
+
+    /// Report scan progress to `handler` instead of the log.
+    pub fn with_progress(mut self, handler: ProgressHandler) -> Self {
+        self.progress = handler;
+        self
+    }
+
+    /// Archive or prune cached records of deleted files as `policy` says,
+    /// instead of following `AGENT_INSIGHTS_MISSING_FILES`.
+    #[cfg(feature = "cache")]
+    pub fn with_missing_files(mut self, policy: MissingFiles) -> Self {
+        self.missing_files = policy;
+        self
+    }
+
     #[cfg(feature = "cache")]
     pub fn cache_stats(&self) -> Result<crate::cache::db::CacheStats> {
         self.cache.get_cache_stats()
     }
 
+    /// Scan every registered agent, adding sessions archived in the cache
+    /// after their files were deleted. Scanners run concurrently on the
+    /// blocking pool, each parsing its files on a bounded set of threads.
     pub async fn collect_all(&self) -> Result<Vec<AgentRecord>> {
-        let mut all_records = Vec::new();
-
-        #[cfg(feature = "cache")]
-        let claude = ClaudeScanner::with_cache(&self.home_dir, Arc::clone(&self.cache));
-        #[cfg(not(feature = "cache"))]
-        let claude = ClaudeScanner::new(&self.home_dir);
+        let scanned: Vec<AgentRecord> = self
+            .scan_all()
+            .await
+            .into_iter()
+            .flat_map(|scan| scan.records)
+            .collect();
+        let all_records = self.with_archived(scanned, None);
 
-        if let Ok(records) = claude.scan() {
-            tracing::info!("Collected {} Claude records", records.len());
-            all_records.extend(records);
-        }
+        tracing::info!("Total collected: {} records", all_records.len());
+        Ok(all_records)
+    }
 
-        let gemini = GeminiScanner::new(&self.home_dir);
-        if let Ok(records) = gemini.scan() {
-            tracing::info!("Collected {} Gemini records", records.len());
-            all_records.extend(records);
+    /// Scan every registered agent and report what happened to each file,
+    /// in registration order.
+    pub async fn scan_all(&self) -> Vec<Scan> {
+        let mut scans = JoinSet::new();
+        for (index, scanner) in self.registry.iter().enumerate() {
+            let scanner = Arc::clone(scanner);
+            let progress = Arc::clone(&self.progress);
+            scans.spawn(async move { (index, scan_blocking(scanner, progress).await) });
         }
 
-        let codex = CodexScanner::new(&self.home_dir);
-        if let Ok(records) = codex.scan() {
-            tracing::info!("Collected {} Codex records", records.len());
-            all_records.extend(records);
+        // Keep registration order so results don't depend on which scan finished first
+        let mut results = Vec::new();
+        while let Some(joined) = scans.join_next().await {
+            match joined {
+                Ok(result) => results.push(result),
+                Err(e) => tracing::warn!("Scan task failed: {}", e),
+            }
         }
+        results.sort_by_key(|(index, _)| *index);
+        #[cfg(feature = "cache")]
+        self.reconcile_cache();
 
-        tracing::info!("Total collected: {} records", all_records.len());
-        Ok(all_records)
+        results
+            .into_iter()
+            .map(|(_, scan)| {
+                let report = &scan.report;
+                tracing::info!("Collected {} {} records", report.records, report.agent);
+                if report.failed > 0 {
+                    tracing::warn!("{} {} files failed to scan", report.failed, report.agent);
+                }
+                scan
+            })
+            .collect()
     }
 
-    /// Refresh the cache by scanning all files, then return records with created_at >= since.
-    /// `since` is an ISO date "YYYY-MM-DD"; None returns all records.
+    /// Refresh the cache by scanning all files, then return records active on or after since,
+    /// with their per-day usage clipped to it. `since` is an ISO date "YYYY-MM-DD"; None
+    /// returns all records.
     #[cfg(feature = "cache")]
     pub async fn collect_since(&self, since: Option<&str>) -> Result<Vec<AgentRecord>> {
         let _ = self.collect_all().await; // populate cache
         self.cache.get_all_records(since)
     }
 
-    pub async fn collect_claude(&self) -> Result<Vec<AgentRecord>> {
+    /// Drop the cache for one agent (by id, e.g. `"claude"`) or for every
+    /// agent, then scan them again so their files are re-parsed.
+    #[cfg(feature = "cache")]
+    pub async fn rebuild_cache(&self, agent: Option<&str>) -> Result<Vec<Scan>> {
+        let scanners: Vec<Arc<dyn AgentScanner>> = match agent {
+            Some(id) => {
+                let scanner = self.registry.get(id)
+                    .ok_or_else(|| anyhow::anyhow!("No agent named {:?}", id))?;
+                vec![Arc::clone(scanner)]
+            }
+            None => self.registry.iter().cloned().collect(),
+        };
+
+        // Flag deleted files first so that their records are not dropped
+        self.reconcile_cache();
+        let mut scans = Vec::new();
+        for scanner in scanners {
+            let dropped = self.cache.clear_agent(&scanner.agent_type())?;
+            tracing::info!("Dropped {} cached {} records", dropped, scanner.agent_type());
+            scans.push(scan_blocking(Arc::clone(&scanner), Arc::clone(&self.progress)).await);
+
+            let cache = Arc::clone(&self.cache);
+            let agent = scanner.agent_type();
+            let reparsed = tokio::task::spawn_blocking(move || reparse_archived(&*scanner, &cache)).await??;
+            if reparsed > 0 {
+                tracing::info!("Re-parsed {} archived {} sessions", reparsed, agent);
+            }
+        }
+        Ok(scans)
+    }
+
+    /// Collect records for a single agent by id (e.g. `"claude"`),
+    /// including its archived sessions. Returns `None` when no scanner is
+    /// registered for that agent.
+    pub async fn collect_agent(&self, id: &str) -> Option<Result<Vec<AgentRecord>>> {
+        let scanner = Arc::clone(self.registry.get(id)?);
+        let agent = scanner.agent_type();
+        let progress = Arc::clone(&self.progress);
+        let scan = scan_blocking(scanner, progress).await;
         #[cfg(feature = "cache")]
-        let scanner = ClaudeScanner::with_cache(&self.home_dir, Arc::clone(&self.cache));
-        #[cfg(not(feature = "cache"))]
-        let scanner = ClaudeScanner::new(&self.home_dir);
-        scanner.scan()
+        self.reconcile_cache();
+        Some(Ok(self.with_archived(scan.records, Some(&agent))))
+    }
+
+    /// Add the cache's archived sessions (of `agent`, or of every agent) to
+    /// freshly scanned records. A session found again under another path,
+    /// e.g. after its file was moved, is not counted twice.
+    #[cfg(feature = "cache")]
+    fn with_archived(&self, mut records: Vec<AgentRecord>, agent: Option<&AgentType>) -> Vec<AgentRecord> {
+        match self.cache.get_archived_records(agent) {
+            Ok(archived) => {
+                let live: std::collections::HashSet<(AgentType, String)> = records
+                    .iter()
+                    .filter_map(|r| Some((r.agent_type.clone(), r.session_id.clone()?)))
+                    .collect();
+                records.extend(archived.into_iter().filter(|r| {
+                    r.session_id.as_ref().is_none_or(|id| !live.contains(&(r.agent_type.clone(), id.clone())))
+                }));
+            }
+            Err(e) => tracing::warn!("Failed to read archived sessions: {}", e),
+        }
+        records
+    }
+
+    #[cfg(not(feature = "cache"))]
+    fn with_archived(&self, records: Vec<AgentRecord>, _agent: Option<&AgentType>) -> Vec<AgentRecord> {
+        records
+    }
+
+    /// After a scan, flag or drop cached records whose files are gone.
+    #[cfg(feature = "cache")]
+    fn reconcile_cache(&self) {
+        match self.cache.reconcile_missing(self.missing_files) {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("{} cached sessions no longer on disk ({:?})", n, self.missing_files),
+            Err(e) => tracing::warn!("Failed to reconcile cache with disk: {}", e),
+        }
     }
+}
+
+/// Run one scanner on the blocking pool. A scan that fails outright is
+/// reported as a failure against the agent rather than a file.
+async fn scan_blocking(scanner: Arc<dyn AgentScanner>, progress: ProgressHandler) -> Scan {
+    let agent = scanner.agent_type();
+    let scanned = tokio::task::spawn_blocking(move || scanner.scan_with_progress(&*progress))
+        .await
+        .map_err(anyhow::Error::from)
+        .and_then(|scanned| scanned);
+    scanned.unwrap_or_else(|e| {
+        let mut report = ScanReport::new(agent.to_string());
+        report.fail(PathBuf::new(), format!("{:#}", e));
+        Scan { records: Vec::new(), report }
+    })
+}
+
+/// Parse archived sessions again from the content kept for them, so that
+/// they follow parser changes even though their files are gone. Returns the
+/// number re-parsed.
+#[cfg(feature = "cache")]
+fn reparse_archived(scanner: &dyn AgentScanner, cache: &CacheDb) -> Result<usize> {
+    let dir = std::env::temp_dir().join(format!("agent-insights-reparse-{}", std::process::id()));
+    // Files holding several sessions are archived as a whole and parsed once
+    let mut by_file: BTreeMap<String, Vec<AgentRecord>> = BTreeMap::new();
+    for archived in cache.get_archived_records(Some(&scanner.agent_type()))? {
+        by_file.entry(archived.file_path.clone()).or_default().push(archived);
+    }
+
+    let reparsed = by_file.into_iter().try_fold(0, |reparsed, (file_path, archived)| {
+        anyhow::Ok(reparsed + reparse_archived_file(scanner, cache, &dir, &file_path, &archived)?)
+    });
+    let _ = std::fs::remove_dir_all(&dir);
+    reparsed
+}
+
+/// Re-parse one archived file from a copy of its content in `dir`, keeping
+/// what the archived records knew that the copy cannot tell. Returns the
+/// number of sessions re-parsed.
+#[cfg(feature = "cache")]
+fn reparse_archived_file(
+    scanner: &dyn AgentScanner,
+    cache: &CacheDb,
+    dir: &std::path::Path,
+    file_path: &str,
+    archived: &[AgentRecord],
+) -> Result<usize> {
+    let Some(content) = cache.get_content(file_path)? else {
+        return Ok(0);
+    };
+    // Keep the file name, which some agents derive session ids from
+    let name = std::path::Path::new(file_path).file_name().unwrap_or_default();
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(name);
+    std::fs::write(&path, &content)?;
+    let file_info = FileInfo {
+        path: path.clone(),
+        created_at: archived[0].created_at,
+        modified_at: archived[0].modified_at,
+        size: content.len() as u64,
+    };
+    let parsed = scanner.read(&file_info);
+    // Reading through the cache stored the temporary copy too
+    cache.forget(&path.to_string_lossy())?;
+    let _ = std::fs::remove_file(&path);
 
-    pub async fn collect_gemini(&self) -> Result<Vec<AgentRecord>> {
-        let scanner = GeminiScanner::new(&self.home_dir);
-        scanner.scan()
+    let mut records = match parsed {
+        Ok(records) => records,
+        Err(e) => {
+            tracing::warn!("Failed to re-parse archived {}: {:#}", file_path, e);
+            return Ok(0);
+        }
+    };
+    // Anything the parser derived from where the copy was put (Aider
+    // session ids and working directories) points back at the original.
+    let temp_path = path.to_string_lossy();
+    let temp_dir = dir.to_string_lossy();
+    let original_dir = std::path::Path::new(file_path).parent().map(|p| p.to_string_lossy().to_string());
+    for (i, record) in records.iter_mut().enumerate() {
+        record.file_path = file_path.to_string();
+        record.session_id = record.session_id.take().map(|id| id.replace(&*temp_path, file_path));
+        if record.cwd.as_deref() == Some(&*temp_dir) {
+            record.cwd = original_dir.clone();
+        }
+        let Some(archived) = archived
+            .iter()
+            .find(|a| a.session_id.is_some() && a.session_id == record.session_id)
+            .or(archived.get(i))
+        else {
+            continue;
+        };
+        // A sub-agent's parent can come from the directory it was in, which
+        // the copy is not
+        record.parent_session_id = record.parent_session_id.take().or_else(|| archived.parent_session_id.clone());
+        record.cwd = record.cwd.take().or_else(|| archived.cwd.clone());
+        record.git_branch = record.git_branch.take().or_else(|| archived.git_branch.clone());
+        record.git_commit = record.git_commit.take().or_else(|| archived.git_commit.clone());
+        record.repo_root = record.repo_root.take().or_else(|| archived.repo_root.clone());
+        record.repo_remote = record.repo_remote.take().or_else(|| archived.repo_remote.clone());
     }
+    cache.cache_records(file_path, &scanner.agent_type(), &file_info.modified_at, &records, scanner.parser_version())?;
+    cache.mark_missing(file_path)?;
+    Ok(records.len())
+}
 
-    pub async fn collect_codex(&self) -> Result<Vec<AgentRecord>> {
-        let scanner = CodexScanner::new(&self.home_dir);
-        scanner.scan()
+/// Log each tenth of a scan, so large histories show they are moving
+/// without a line per file.
+fn log_progress(progress: ScanProgress) {
+    let ScanProgress { agent, done, total } = progress;
+    if total < 100 {
+        return;
+    }
+    if done == total || done * 10 / total != (done - 1) * 10 / total {
+        tracing::info!("Scanning {}: {}/{} files", agent, done, total);
     }
 }
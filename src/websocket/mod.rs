@@ -41,10 +41,10 @@ async fn handle_socket(socket: WebSocket, state: Arc<WsState>) {
 
     let mut send_task = tokio::spawn(async move {
         while let Ok(msg) = rx.recv().await {
-            if let Ok(json) = serde_json::to_string(&msg) {
-                if sender.send(Message::Text(json)).await.is_err() {
-                    break;
-                }
+            if let Ok(json) = serde_json::to_string(&msg)
+                && sender.send(Message::Text(json)).await.is_err()
+            {
+                break;
             }
         }
     });
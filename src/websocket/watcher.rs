@@ -1,25 +1,29 @@
+use crate::agents::ScannerRegistry;
 use notify::{Config, Event, RecommendedWatcher, RecursiveMode, Watcher};
-use std::path::PathBuf;
+use std::sync::Arc;
 
 use super::{UpdateMessage, UpdateSender};
 
 pub struct FileWatcher {
     tx: UpdateSender,
+    registry: Arc<ScannerRegistry>,
     watcher: Option<RecommendedWatcher>,
 }
 
 impl FileWatcher {
-    pub fn new(tx: UpdateSender) -> Self {
-        Self { tx, watcher: None }
+    pub fn new(tx: UpdateSender, registry: Arc<ScannerRegistry>) -> Self {
+        Self { tx, registry, watcher: None }
     }
 
-    pub fn start(&mut self, paths: Vec<(String, PathBuf)>) -> notify::Result<()> {
+    /// Watch the roots of every registered scanner.
+    pub fn start(&mut self) -> notify::Result<()> {
         let tx = self.tx.clone();
+        let registry = Arc::clone(&self.registry);
 
         let watcher = RecommendedWatcher::new(
             move |res: Result<Event, notify::Error>| {
                 if let Ok(event) = res {
-                    handle_event(event, &tx);
+                    handle_event(event, &tx, &registry);
                 }
             },
             Config::default(),
@@ -27,9 +31,12 @@ impl FileWatcher {
 
         self.watcher = Some(watcher);
 
-        for (agent, path) in paths {
-            if path.exists() {
-                if let Some(ref mut w) = self.watcher {
+        for scanner in self.registry.iter() {
+            let agent = scanner.agent_type();
+            for path in scanner.watch_roots() {
+                if path.exists()
+                    && let Some(ref mut w) = self.watcher
+                {
                     if let Err(e) = w.watch(&path, RecursiveMode::Recursive) {
                         tracing::warn!("Failed to watch {:?} for {}: {}", path, agent, e);
                     } else {
@@ -43,38 +50,18 @@ impl FileWatcher {
     }
 }
 
-fn handle_event(event: Event, tx: &UpdateSender) {
+fn handle_event(event: Event, tx: &UpdateSender, registry: &ScannerRegistry) {
     use notify::EventKind;
 
-    match event.kind {
-        EventKind::Create(_) | EventKind::Modify(_) => {
-            for path in event.paths {
-                if let Some(ext) = path.extension() {
-                    if ext == "json" || ext == "jsonl" {
-                        if let Some(agent) = detect_agent_from_path(&path) {
-                            let msg = UpdateMessage::FileAdded {
-                                agent,
-                                file_path: path.to_string_lossy().to_string(),
-                            };
-                            let _ = tx.send(msg);
-                        }
-                    }
-                }
+    if let EventKind::Create(_) | EventKind::Modify(_) = event.kind {
+        for path in event.paths {
+            if let Some(agent) = registry.agent_for_path(&path) {
+                let msg = UpdateMessage::FileAdded {
+                    agent: agent.to_string(),
+                    file_path: path.to_string_lossy().to_string(),
+                };
+                let _ = tx.send(msg);
             }
         }
-        _ => {}
-    }
-}
-
-fn detect_agent_from_path(path: &PathBuf) -> Option<String> {
-    let path_str = path.to_string_lossy();
-    if path_str.contains("/.claude/") {
-        Some("Claude".to_string())
-    } else if path_str.contains("/.gemini/") {
-        Some("Gemini".to_string())
-    } else if path_str.contains("/.codex/") {
-        Some("Codex".to_string())
-    } else {
-        None
     }
 }
@@ -11,17 +11,18 @@ export function Dashboard() {
   const [loading, setLoading] = useState(true)
   const [error, setError] = useState<string | null>(null)
   const [toasts, setToasts] = useState<ToastProps[]>([])
+  const [splitSubagents, setSplitSubagents] = useState(false)
 
   const loadHeatmaps = useCallback(async () => {
     try {
-      const data = await fetchAllHeatmaps()
+      const data = await fetchAllHeatmaps(splitSubagents)
       setHeatmaps(data)
       setLoading(false)
     } catch (err) {
       setError(err instanceof Error ? err.message : 'Failed to load data')
       setLoading(false)
     }
-  }, [])
+  }, [splitSubagents])
 
   const addToast = useCallback((toast: Omit<ToastProps, 'onClose'>) => {
     setToasts(prev => [...prev, { ...toast, onClose: () => {
@@ -151,11 +152,21 @@ export function Dashboard() {
       <div className="mb-8">
         <div className="flex items-center justify-between mb-2">
           <h1 className="text-3xl font-bold">Agent Insights</h1>
-          <div className="flex items-center gap-2">
-            <div className={`h-2 w-2 rounded-full ${isConnected ? 'bg-green-500' : 'bg-gray-400'}`} />
-            <span className="text-sm text-muted-foreground">
-              {isConnected ? 'Live' : 'Offline'}
-            </span>
+          <div className="flex items-center gap-4">
+            <label className="flex items-center gap-2 text-sm text-muted-foreground cursor-pointer">
+              <input
+                type="checkbox"
+                checked={splitSubagents}
+                onChange={(e) => setSplitSubagents(e.target.checked)}
+              />
+              Show sub-agents separately
+            </label>
+            <div className="flex items-center gap-2">
+              <div className={`h-2 w-2 rounded-full ${isConnected ? 'bg-green-500' : 'bg-gray-400'}`} />
+              <span className="text-sm text-muted-foreground">
+                {isConnected ? 'Live' : 'Offline'}
+              </span>
+            </div>
           </div>
         </div>
         <p className="text-muted-foreground">
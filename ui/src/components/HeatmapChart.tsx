@@ -1,22 +1,44 @@
 import { useState } from 'react'
 import { Card, CardContent, CardDescription, CardHeader, CardTitle } from '@/components/ui/card'
 import { Switch } from '@/components/ui/switch'
-import type { HeatmapData } from '@/types/insight'
+import { fetchSessions } from '@/services/api'
+import type { DayActivity, HeatmapData, SessionSummary, ToolCallStats } from '@/types/insight'
 
-interface CellData {
-  date: string
-  count: number
-  size: number
+interface CellData extends DayActivity {
   x: number
   y: number
 }
 
+const formatDuration = (ms: number): string =>
+  ms < 1000 ? `${ms} ms` : `${(ms / 1000).toFixed(1)} s`
+
+const formatToolOutcomes = (tool: ToolCallStats): string => {
+  const parts = [`${tool.error_count} errors`, `${tool.rejected_count} rejected`]
+  if (tool.p50_duration_ms != null) {
+    parts.push(`p50 ${formatDuration(tool.p50_duration_ms)}`)
+  }
+  if (tool.p95_duration_ms != null) {
+    parts.push(`p95 ${formatDuration(tool.p95_duration_ms)}`)
+  }
+  return parts.join(' · ')
+}
+
 interface HeatmapChartProps {
   data: HeatmapData
 }
 
 export function HeatmapChart({ data }: HeatmapChartProps) {
   const [showDetails, setShowDetails] = useState(true)
+  const [selectedDate, setSelectedDate] = useState<string | null>(null)
+  const [sessions, setSessions] = useState<SessionSummary[]>([])
+
+  const selectDay = (date: string) => {
+    setSelectedDate(date)
+    setSessions([])
+    fetchSessions(date, data.agent)
+      .then(setSessions)
+      .catch(() => setSessions([]))
+  }
 
   const generateYearGrid = (): CellData[] => {
     const cells: CellData[] = []
@@ -24,9 +46,9 @@ export function HeatmapChart({ data }: HeatmapChartProps) {
     const oneYearAgo = new Date(today)
     oneYearAgo.setFullYear(today.getFullYear() - 1)
 
-    const activityMap = new Map<string, { count: number; size: number }>()
-    data.data.forEach(({ date, count, size }) => {
-      activityMap.set(date, { count, size })
+    const activityMap = new Map<string, DayActivity>()
+    data.data.forEach((activity) => {
+      activityMap.set(activity.date, activity)
     })
 
     let currentDate = new Date(oneYearAgo)
@@ -35,12 +57,17 @@ export function HeatmapChart({ data }: HeatmapChartProps) {
 
     while (currentDate <= today) {
       const dateStr = currentDate.toISOString().split('T')[0]
-      const activity = activityMap.get(dateStr) || { count: 0, size: 0 }
+      const activity = activityMap.get(dateStr) || {
+        date: dateStr,
+        sessions: 0,
+        messages: 0,
+        tokens: 0,
+        tool_calls: 0,
+        size: 0,
+      }
 
       cells.push({
-        date: dateStr,
-        count: activity.count,
-        size: activity.size,
+        ...activity,
         x: week,
         y: day,
       })
@@ -164,14 +191,15 @@ export function HeatmapChart({ data }: HeatmapChartProps) {
                 {cells.map((cell) => (
                   <div
                     key={cell.date}
-                    className={`absolute rounded-sm ${getColor(cell.count)} hover:ring-2 hover:ring-primary cursor-pointer transition-all`}
+                    className={`absolute rounded-sm ${getColor(cell.sessions)} hover:ring-2 hover:ring-primary cursor-pointer transition-all`}
                     style={{
                       left: `${cell.x * (cellSize + cellGap)}px`,
                       top: `${cell.y * (cellSize + cellGap)}px`,
                       width: `${cellSize}px`,
                       height: `${cellSize}px`,
                     }}
-                    title={`${cell.date}: ${cell.count} file${cell.count !== 1 ? 's' : ''} (${formatBytes(cell.size)})`}
+                    onClick={() => cell.sessions > 0 && selectDay(cell.date)}
+                    title={`${cell.date}: ${cell.sessions} session${cell.sessions !== 1 ? 's' : ''} · ${cell.messages.toLocaleString()} messages · ${cell.tokens.toLocaleString()} tokens · ${cell.tool_calls.toLocaleString()} tool calls`}
                   />
                 ))}
               </div>
@@ -192,6 +220,28 @@ export function HeatmapChart({ data }: HeatmapChartProps) {
           </div>
         </div>
 
+        {/* Sessions on the selected day */}
+        {selectedDate && (
+          <div className="mt-4">
+            <h4 className="text-sm font-semibold mb-2">Sessions on {selectedDate}</h4>
+            <div className="space-y-1">
+              {sessions.map((session) => (
+                <div
+                  key={session.session_id ?? session.started_at}
+                  className="flex items-center justify-between text-sm"
+                  title={session.cwd ?? undefined}
+                >
+                  <span className="truncate text-muted-foreground">
+                    {session.title ?? 'Untitled session'}
+                    {session.source_missing && <span className="ml-1 text-xs">(archived)</span>}
+                  </span>
+                  <span className="ml-2 font-mono font-medium">{formatNumber(session.tokens)}</span>
+                </div>
+              ))}
+            </div>
+          </div>
+        )}
+
         {/* Statistics */}
         {showDetails && (
           <div className="mt-6 grid grid-cols-1 md:grid-cols-2 gap-4">
@@ -201,9 +251,42 @@ export function HeatmapChart({ data }: HeatmapChartProps) {
                 <h4 className="text-sm font-semibold mb-2">Top Tool Calls</h4>
                 <div className="space-y-1">
                   {data.tool_calls.slice(0, 5).map((tool) => (
-                    <div key={tool.tool_name} className="flex items-center justify-between text-sm">
+                    <div
+                      key={tool.tool_name}
+                      className="flex items-center justify-between text-sm"
+                      title={formatToolOutcomes(tool)}
+                    >
                       <span className="text-muted-foreground">{tool.tool_name}</span>
-                      <span className="font-mono font-medium">{tool.count.toLocaleString()}</span>
+                      <span className="font-mono font-medium">
+                        {tool.count.toLocaleString()}
+                        {tool.error_count > 0 && (
+                          <span className="ml-2 text-red-500">{tool.error_count.toLocaleString()} err</span>
+                        )}
+                      </span>
+                    </div>
+                  ))}
+                </div>
+              </div>
+            )}
+
+            {/* CLI Versions */}
+            {data.versions && data.versions.length > 0 && (
+              <div>
+                <h4 className="text-sm font-semibold mb-2">CLI Versions</h4>
+                <div className="space-y-1">
+                  {data.versions.slice(0, 5).map((v) => (
+                    <div
+                      key={v.version}
+                      className="flex items-center justify-between text-sm"
+                      title={`${formatNumber(v.tokens)} tokens · ${v.tool_errors} of ${v.tool_calls} tool calls failed`}
+                    >
+                      <span className="text-muted-foreground">{v.version}</span>
+                      <span className="font-mono font-medium">
+                        {v.sessions.toLocaleString()}
+                        {v.tool_errors > 0 && (
+                          <span className="ml-2 text-red-500">{(v.tool_error_rate * 100).toFixed(1)}% err</span>
+                        )}
+                      </span>
                     </div>
                   ))}
                 </div>
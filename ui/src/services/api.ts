@@ -1,9 +1,10 @@
-import type { HeatmapData, AllHeatmapsResponse } from '@/types/insight'
+import type { HeatmapData, AllHeatmapsResponse, SessionSummary } from '@/types/insight'
 
 const API_BASE_URL = 'http://127.0.0.1:3001/api'
 
-export async function fetchAllHeatmaps(): Promise<AllHeatmapsResponse> {
-  const response = await fetch(`${API_BASE_URL}/heatmaps`)
+export async function fetchAllHeatmaps(splitSubagents = false): Promise<AllHeatmapsResponse> {
+  const query = splitSubagents ? '?split_subagents=true' : ''
+  const response = await fetch(`${API_BASE_URL}/heatmaps${query}`)
   if (!response.ok) {
     throw new Error('Failed to fetch heatmaps')
   }
@@ -17,3 +18,15 @@ export async function fetchAgentHeatmap(agent: string): Promise<HeatmapData> {
   }
   return response.json()
 }
+
+export async function fetchSessions(date: string, agent?: string): Promise<SessionSummary[]> {
+  const params = new URLSearchParams({ date })
+  if (agent) {
+    params.set('agent', agent.toLowerCase())
+  }
+  const response = await fetch(`${API_BASE_URL}/sessions?${params}`)
+  if (!response.ok) {
+    throw new Error(`Failed to fetch sessions for ${date}`)
+  }
+  return response.json()
+}
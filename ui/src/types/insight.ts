@@ -1,12 +1,28 @@
 export interface DayActivity {
   date: string
-  count: number
+  sessions: number
+  messages: number
+  tokens: number
+  tool_calls: number
   size: number
 }
 
 export interface ToolCallStats {
   tool_name: string
   count: number
+  error_count: number
+  rejected_count: number
+  p50_duration_ms?: number | null
+  p95_duration_ms?: number | null
+}
+
+export interface VersionStats {
+  version: string
+  sessions: number
+  tokens: number
+  tool_calls: number
+  tool_errors: number
+  tool_error_rate: number
 }
 
 export interface TokenStats {
@@ -26,6 +42,24 @@ export interface HeatmapData {
   total_size: number
   tool_calls: ToolCallStats[]
   token_stats: TokenStats
+  models: string[]
+  versions: VersionStats[]
+}
+
+export interface SessionSummary {
+  agent: string
+  session_id?: string | null
+  parent_session_id?: string | null
+  title?: string | null
+  started_at: string
+  ended_at: string
+  model?: string | null
+  cwd?: string | null
+  repo_root?: string | null
+  git_branch?: string | null
+  tokens: number
+  tool_calls: number
+  source_missing: boolean
 }
 
 export interface AllHeatmapsResponse {